@@ -4,6 +4,7 @@ use crate::escrow_storage;
 use crate::escrow_storage::*;
 use soroban_sdk::{token, Address, Env, String, Vec};
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_escrow(
     env: &Env,
     buyer: &Address,
@@ -12,11 +13,22 @@ pub fn create_escrow(
     token: &Address,
     amount: u128,
     description: String,
+    arbitrator_fee: Option<ArbitratorFee>,
 ) -> Result<u64, ContractError> {
     if amount == 0 {
         return Err(ContractError::InvalidAmount);
     }
 
+    let arbitrator_fee = arbitrator_fee.unwrap_or(ArbitratorFee {
+        amount: FeeAmount::None,
+        policy: FeePolicy::Buyer,
+    });
+    match arbitrator_fee.amount {
+        FeeAmount::Flat(flat) if flat > amount => return Err(ContractError::InvalidAmount),
+        FeeAmount::Bps(bps) if bps > BPS_DENOMINATOR => return Err(ContractError::InvalidInput),
+        _ => {}
+    }
+
     if buyer == seller || buyer == arbitrator || seller == arbitrator {
         return Err(ContractError::InvalidInput);
     }
@@ -38,6 +50,7 @@ pub fn create_escrow(
         completed_at: None,
         disputed_at: None,
         dispute_reason: None,
+        arbitrator_fee,
     };
 
     set_escrow(env, &escrow);
@@ -158,7 +171,7 @@ pub fn arbitrate(
     env: &Env,
     escrow_id: u64,
     arbitrator: &Address,
-    release_to_seller: bool,
+    seller_share: SellerShare,
 ) -> Result<(), ContractError> {
     let mut escrow = get_escrow(env, escrow_id)?;
 
@@ -170,15 +183,42 @@ pub fn arbitrate(
         return Err(ContractError::EscrowNotDisputed);
     }
 
+    let seller_amount = match seller_share {
+        SellerShare::Bps(bps) => {
+            if bps > BPS_DENOMINATOR {
+                return Err(ContractError::InvalidInput);
+            }
+            escrow.amount * bps as u128 / BPS_DENOMINATOR as u128
+        }
+        SellerShare::Amount(amount) => {
+            if amount > escrow.amount {
+                return Err(ContractError::InvalidAmount);
+            }
+            amount
+        }
+    };
+    let mut buyer_amount = escrow.amount - seller_amount;
+    let mut seller_amount = seller_amount;
+
+    // Deduct the arbitrator fee from the parties' awards
+    let (buyer_fee, seller_fee) =
+        split_arbitrator_fee(escrow.amount, &escrow.arbitrator_fee, buyer_amount, seller_amount);
+    buyer_amount -= buyer_fee;
+    seller_amount -= seller_fee;
+    let fee_amount = buyer_fee + seller_fee;
+
     let contract_address = env.current_contract_address();
     let token_client = token::Client::new(env, &escrow.token);
 
-    if release_to_seller {
-        // Release funds to seller
-        token_client.transfer(&contract_address, &escrow.seller, &(escrow.amount as i128));
-    } else {
-        // Refund to buyer
-        token_client.transfer(&contract_address, &escrow.buyer, &(escrow.amount as i128));
+    // Pay the arbitrator, then the seller's share, and refund the rest to the buyer
+    if fee_amount > 0 {
+        token_client.transfer(&contract_address, &escrow.arbitrator, &(fee_amount as i128));
+    }
+    if seller_amount > 0 {
+        token_client.transfer(&contract_address, &escrow.seller, &(seller_amount as i128));
+    }
+    if buyer_amount > 0 {
+        token_client.transfer(&contract_address, &escrow.buyer, &(buyer_amount as i128));
     }
 
     escrow.status = EscrowStatus::Completed;
@@ -190,13 +230,49 @@ pub fn arbitrate(
         env,
         escrow_id,
         arbitrator.clone(),
-        release_to_seller,
         escrow.amount,
+        buyer_amount,
+        seller_amount,
+        fee_amount,
     );
 
     Ok(())
 }
 
+/// Splits the arbitrator fee on `amount` into the parts paid by the buyer and the
+/// seller out of their awards, following the fee policy. A part larger than the
+/// party's award is capped at the award and the other party pays the difference.
+fn split_arbitrator_fee(
+    amount: u128,
+    fee: &ArbitratorFee,
+    buyer_amount: u128,
+    seller_amount: u128,
+) -> (u128, u128) {
+    let fee_amount = match fee.amount {
+        FeeAmount::None => 0,
+        FeeAmount::Flat(flat) => flat,
+        FeeAmount::Bps(bps) => amount * bps as u128 / BPS_DENOMINATOR as u128,
+    };
+
+    let (mut buyer_fee, mut seller_fee) = match fee.policy {
+        FeePolicy::Loser if buyer_amount < seller_amount => (fee_amount, 0),
+        FeePolicy::Loser if seller_amount < buyer_amount => (0, fee_amount),
+        FeePolicy::Loser | FeePolicy::Split => (fee_amount - fee_amount / 2, fee_amount / 2),
+        FeePolicy::Buyer => (fee_amount, 0),
+    };
+
+    if buyer_fee > buyer_amount {
+        seller_fee += buyer_fee - buyer_amount;
+        buyer_fee = buyer_amount;
+    }
+    if seller_fee > seller_amount {
+        buyer_fee += seller_fee - seller_amount;
+        seller_fee = seller_amount;
+    }
+
+    (buyer_fee, seller_fee)
+}
+
 pub fn refund(
     env: &Env,
     escrow_id: u64,
@@ -26,8 +26,57 @@ pub struct Escrow {
     pub completed_at: Option<u64>,
     pub disputed_at: Option<u64>,
     pub dispute_reason: Option<String>,
+    pub arbitrator_fee: ArbitratorFee,
 }
 
+/// Part of the escrowed amount an arbitrator awards to the seller. The buyer is
+/// refunded the rest.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SellerShare {
+    /// Share in basis points of the escrowed amount, rounded down.
+    Bps(u32),
+    /// Absolute amount, at most the escrowed amount.
+    Amount(u128),
+}
+
+/// Fee the parties agree to pay the arbitrator if a dispute is arbitrated. It is
+/// deducted from the escrowed amount, and nothing is charged if the escrow never
+/// reaches arbitration.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArbitratorFee {
+    pub amount: FeeAmount,
+    pub policy: FeePolicy,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FeeAmount {
+    /// No fee is charged.
+    None,
+    /// Flat fee, at most the escrowed amount.
+    Flat(u128),
+    /// Fee in basis points of the escrowed amount, rounded down.
+    Bps(u32),
+}
+
+/// Who bears the arbitrator fee. A party never pays more than the ruling awards
+/// them; the other party covers the rest.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FeePolicy {
+    /// The party awarded the smaller part of the escrow pays, an even ruling splits the fee.
+    Loser,
+    /// Buyer and seller each pay half, the buyer pays the odd unit.
+    Split,
+    /// The buyer pays the whole fee.
+    Buyer,
+}
+
+/// Basis points making up the whole escrowed amount.
+pub const BPS_DENOMINATOR: u32 = 10_000;
+
 // Storage key types
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
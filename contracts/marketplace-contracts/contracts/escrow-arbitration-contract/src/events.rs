@@ -42,8 +42,10 @@ pub struct DisputeRaisedEvent {
 pub struct ArbitrationCompletedEvent {
     pub escrow_id: u64,
     pub arbitrator: Address,
-    pub release_to_seller: bool,
     pub amount: u128,
+    pub buyer_amount: u128,
+    pub seller_amount: u128,
+    pub arbitrator_fee: u128,
 }
 
 #[contracttype]
@@ -119,14 +121,18 @@ pub fn emit_arbitration_completed(
     env: &Env,
     escrow_id: u64,
     arbitrator: Address,
-    release_to_seller: bool,
     amount: u128,
+    buyer_amount: u128,
+    seller_amount: u128,
+    arbitrator_fee: u128,
 ) {
     let event = ArbitrationCompletedEvent {
         escrow_id,
         arbitrator,
-        release_to_seller,
         amount,
+        buyer_amount,
+        seller_amount,
+        arbitrator_fee,
     };
     env.events().publish(("arbitration_completed",), event);
 }
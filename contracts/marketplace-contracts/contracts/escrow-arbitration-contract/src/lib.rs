@@ -30,7 +30,9 @@ impl EscrowArbitrationContract {
         Ok(())
     }
 
-    /// Create a new escrow transaction
+    /// Create a new escrow transaction. An arbitrator fee has to be agreed to by both
+    /// buyer and seller.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_escrow(
         env: Env,
         buyer: Address,
@@ -39,9 +41,13 @@ impl EscrowArbitrationContract {
         token: Address,
         amount: u128,
         description: String,
+        arbitrator_fee: Option<escrow_storage::ArbitratorFee>,
     ) -> Result<u64, ContractError> {
         buyer.require_auth();
-        contract::create_escrow(&env, &buyer, &seller, &arbitrator, &token, amount, description)
+        if arbitrator_fee.is_some() {
+            seller.require_auth();
+        }
+        contract::create_escrow(&env, &buyer, &seller, &arbitrator, &token, amount, description, arbitrator_fee)
     }
 
     /// Deposit funds into escrow
@@ -75,15 +81,16 @@ impl EscrowArbitrationContract {
         contract::raise_dispute(&env, escrow_id, &disputer, reason)
     }
 
-    /// Arbitrate dispute (arbitrator only)
+    /// Arbitrate dispute (arbitrator only), awarding `seller_share` of the escrowed
+    /// amount to the seller and refunding the rest to the buyer
     pub fn arbitrate(
         env: Env,
         escrow_id: u64,
         arbitrator: Address,
-        release_to_seller: bool,
+        seller_share: escrow_storage::SellerShare,
     ) -> Result<(), ContractError> {
         arbitrator.require_auth();
-        contract::arbitrate(&env, escrow_id, &arbitrator, release_to_seller)
+        contract::arbitrate(&env, escrow_id, &arbitrator, seller_share)
     }
 
     /// Refund funds to buyer
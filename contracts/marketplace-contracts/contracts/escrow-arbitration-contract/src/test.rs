@@ -2,11 +2,14 @@
 extern crate std;
 
 use crate::{EscrowArbitrationContract, EscrowArbitrationContractClient};
-use crate::escrow_storage::EscrowStatus;
+use crate::escrow_storage::{
+    ArbitratorFee, EscrowStatus, FeeAmount, FeePolicy, SellerShare, BPS_DENOMINATOR,
+};
 use crate::error::ContractError;
+use crate::events::ArbitrationCompletedEvent;
 use soroban_sdk::{
-    testutils::Address as _,
-    token, Address, Env, String,
+    testutils::{Address as _, Events, MockAuth, MockAuthInvoke},
+    token, Address, Env, IntoVal, String,
 };
 use token::Client as TokenClient;
 use token::StellarAssetClient as TokenAdminClient;
@@ -80,9 +83,26 @@ impl<'a> EscrowTest<'a> {
             &self.token.address,
             &ESCROW_AMOUNT,
             &self.description,
+            &None,
         )
     }
     
+    fn create_escrow_with_fee(&self, amount: FeeAmount, policy: FeePolicy) -> u64 {
+        let escrow_id = self.contract.create_escrow(
+            &self.buyer,
+            &self.seller,
+            &self.arbitrator,
+            &self.token.address,
+            &ESCROW_AMOUNT,
+            &self.description,
+            &Some(ArbitratorFee { amount, policy }),
+        );
+        self.deposit_funds(escrow_id);
+        let dispute_reason = String::from_str(&self.env, "Dispute reason");
+        self.contract.raise_dispute(&escrow_id, &self.buyer, &dispute_reason);
+        escrow_id
+    }
+    
     fn deposit_funds(&self, escrow_id: u64) {
         self.contract.deposit(&escrow_id, &self.buyer);
     }
@@ -139,6 +159,7 @@ fn test_create_escrow_zero_amount() {
         &test.token.address,
         &0,
         &test.description,
+        &None,
     );
     assert!(result.is_err());
     assert_eq!(result.unwrap_err(), Ok(ContractError::InvalidAmount));
@@ -154,6 +175,7 @@ fn test_create_escrow_same_buyer_seller() {
         &test.token.address,
         &ESCROW_AMOUNT,
         &test.description,
+        &None,
     );
     assert!(result.is_err());
     assert_eq!(result.unwrap_err(), Ok(ContractError::InvalidInput));
@@ -169,6 +191,7 @@ fn test_create_escrow_same_buyer_arbitrator() {
         &test.token.address,
         &ESCROW_AMOUNT,
         &test.description,
+        &None,
     );
     assert!(result.is_err());
     assert_eq!(result.unwrap_err(), Ok(ContractError::InvalidInput));
@@ -184,6 +207,7 @@ fn test_create_escrow_same_seller_arbitrator() {
         &test.token.address,
         &ESCROW_AMOUNT,
         &test.description,
+        &None,
     );
     assert!(result.is_err());
     assert_eq!(result.unwrap_err(), Ok(ContractError::InvalidInput));
@@ -342,7 +366,7 @@ fn test_arbitrate_release_to_seller() {
     let seller_balance_before = test.token.balance(&test.seller);
     let contract_balance_before = test.token.balance(&test.contract.address);
     
-    test.contract.arbitrate(&escrow_id, &test.arbitrator, &true);
+    test.contract.arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(BPS_DENOMINATOR));
     
     assert_eq!(
         test.token.balance(&test.seller),
@@ -368,7 +392,7 @@ fn test_arbitrate_refund_to_buyer() {
     let buyer_balance_before = test.token.balance(&test.buyer);
     let contract_balance_before = test.token.balance(&test.contract.address);
     
-    test.contract.arbitrate(&escrow_id, &test.arbitrator, &false);
+    test.contract.arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(0));
     
     assert_eq!(
         test.token.balance(&test.buyer),
@@ -383,6 +407,206 @@ fn test_arbitrate_refund_to_buyer() {
     assert_eq!(escrow.status, EscrowStatus::Completed);
 }
 
+#[test]
+fn test_arbitrate_split_bps() {
+    let (test, escrow_id) = EscrowTest::setup_with_funded_escrow();
+    
+    let dispute_reason = String::from_str(&test.env, "Dispute reason");
+    test.contract.raise_dispute(&escrow_id, &test.buyer, &dispute_reason);
+    
+    let buyer_balance_before = test.token.balance(&test.buyer);
+    
+    test.contract.arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(7_000));
+    
+    assert_eq!(test.token.balance(&test.seller), 700);
+    assert_eq!(test.token.balance(&test.buyer), buyer_balance_before + 300);
+    assert_eq!(test.token.balance(&test.contract.address), 0);
+    
+    let escrow = test.contract.get_escrow(&escrow_id);
+    assert_eq!(escrow.status, EscrowStatus::Completed);
+}
+
+#[test]
+fn test_arbitrate_split_amount() {
+    let (test, escrow_id) = EscrowTest::setup_with_funded_escrow();
+    
+    let dispute_reason = String::from_str(&test.env, "Dispute reason");
+    test.contract.raise_dispute(&escrow_id, &test.seller, &dispute_reason);
+    
+    let buyer_balance_before = test.token.balance(&test.buyer);
+    
+    test.contract.arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Amount(250));
+    
+    assert_eq!(test.token.balance(&test.seller), 250);
+    assert_eq!(test.token.balance(&test.buyer), buyer_balance_before + 750);
+    
+    let (_, _, data) = test.env.events().all().last().unwrap();
+    let event: ArbitrationCompletedEvent = data.into_val(&test.env);
+    assert_eq!(event.amount, ESCROW_AMOUNT);
+    assert_eq!(event.buyer_amount, 750);
+    assert_eq!(event.seller_amount, 250);
+}
+
+#[test]
+fn test_arbitrate_invalid_share() {
+    let (test, escrow_id) = EscrowTest::setup_with_funded_escrow();
+    
+    let dispute_reason = String::from_str(&test.env, "Dispute reason");
+    test.contract.raise_dispute(&escrow_id, &test.buyer, &dispute_reason);
+    
+    let result = test.contract.try_arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(BPS_DENOMINATOR + 1));
+    assert_eq!(result.unwrap_err(), Ok(ContractError::InvalidInput));
+    
+    let result = test.contract.try_arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Amount(ESCROW_AMOUNT + 1));
+    assert_eq!(result.unwrap_err(), Ok(ContractError::InvalidAmount));
+    
+    let escrow = test.contract.get_escrow(&escrow_id);
+    assert_eq!(escrow.status, EscrowStatus::Disputed);
+}
+
+#[test]
+fn test_arbitrate_fee_paid_by_loser() {
+    let test = EscrowTest::setup();
+    let escrow_id = test.create_escrow_with_fee(FeeAmount::Bps(500), FeePolicy::Loser);
+    
+    let escrow = test.contract.get_escrow(&escrow_id);
+    assert_eq!(escrow.arbitrator_fee, ArbitratorFee { amount: FeeAmount::Bps(500), policy: FeePolicy::Loser });
+    
+    test.contract.arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(7_000));
+    
+    assert_eq!(test.token.balance(&test.arbitrator), 50);
+    assert_eq!(test.token.balance(&test.seller), 700);
+    assert_eq!(test.token.balance(&test.buyer), 250);
+    assert_eq!(test.token.balance(&test.contract.address), 0);
+    
+    let (_, _, data) = test.env.events().all().last().unwrap();
+    let event: ArbitrationCompletedEvent = data.into_val(&test.env);
+    assert_eq!(event.arbitrator_fee, 50);
+    assert_eq!(event.buyer_amount, 250);
+    assert_eq!(event.seller_amount, 700);
+}
+
+#[test]
+fn test_arbitrate_fee_split() {
+    let test = EscrowTest::setup();
+    let escrow_id = test.create_escrow_with_fee(FeeAmount::Flat(51), FeePolicy::Split);
+    
+    test.contract.arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(5_000));
+    
+    assert_eq!(test.token.balance(&test.arbitrator), 51);
+    assert_eq!(test.token.balance(&test.seller), 475);
+    assert_eq!(test.token.balance(&test.buyer), 474);
+}
+
+#[test]
+fn test_arbitrate_fee_capped_by_award() {
+    let test = EscrowTest::setup();
+    let escrow_id = test.create_escrow_with_fee(FeeAmount::Flat(100), FeePolicy::Buyer);
+    
+    // The buyer is awarded nothing, so the seller covers the fee
+    test.contract.arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(BPS_DENOMINATOR));
+    
+    assert_eq!(test.token.balance(&test.arbitrator), 100);
+    assert_eq!(test.token.balance(&test.seller), 900);
+    assert_eq!(test.token.balance(&test.buyer), 0);
+}
+
+#[test]
+fn test_fee_not_charged_without_arbitration() {
+    let test = EscrowTest::setup();
+    let escrow_id = test.contract.create_escrow(
+        &test.buyer,
+        &test.seller,
+        &test.arbitrator,
+        &test.token.address,
+        &ESCROW_AMOUNT,
+        &test.description,
+        &Some(ArbitratorFee { amount: FeeAmount::Flat(100), policy: FeePolicy::Buyer }),
+    );
+    test.deposit_funds(escrow_id);
+    
+    test.contract.release_funds(&escrow_id, &test.buyer);
+    
+    assert_eq!(test.token.balance(&test.arbitrator), 0);
+    assert_eq!(test.token.balance(&test.seller), ESCROW_AMOUNT as i128);
+}
+
+#[test]
+fn test_create_escrow_fee_requires_seller_auth() {
+    let test = EscrowTest::setup();
+    let fee = ArbitratorFee { amount: FeeAmount::Bps(500), policy: FeePolicy::Loser };
+    let invoke = MockAuthInvoke {
+        contract: &test.contract.address,
+        fn_name: "create_escrow",
+        args: (
+            test.buyer.clone(),
+            test.seller.clone(),
+            test.arbitrator.clone(),
+            test.token.address.clone(),
+            ESCROW_AMOUNT,
+            test.description.clone(),
+            Some(fee.clone()),
+        )
+            .into_val(&test.env),
+        sub_invokes: &[],
+    };
+    
+    // Only the buyer authorizes the creation, which fails without the seller
+    test.env.mock_auths(&[MockAuth { address: &test.buyer, invoke: &invoke }]);
+    let result = test.contract.try_create_escrow(
+        &test.buyer,
+        &test.seller,
+        &test.arbitrator,
+        &test.token.address,
+        &ESCROW_AMOUNT,
+        &test.description,
+        &Some(fee.clone()),
+    );
+    assert!(result.is_err());
+    
+    // With the seller's authorization as well the escrow is created
+    test.env.mock_auths(&[
+        MockAuth { address: &test.buyer, invoke: &invoke },
+        MockAuth { address: &test.seller, invoke: &invoke },
+    ]);
+    let escrow_id = test.contract.create_escrow(
+        &test.buyer,
+        &test.seller,
+        &test.arbitrator,
+        &test.token.address,
+        &ESCROW_AMOUNT,
+        &test.description,
+        &Some(fee.clone()),
+    );
+    assert_eq!(test.contract.get_escrow(&escrow_id).arbitrator_fee, fee);
+}
+
+#[test]
+fn test_create_escrow_invalid_fee() {
+    let test = EscrowTest::setup();
+    let result = test.contract.try_create_escrow(
+        &test.buyer,
+        &test.seller,
+        &test.arbitrator,
+        &test.token.address,
+        &ESCROW_AMOUNT,
+        &test.description,
+        &Some(ArbitratorFee { amount: FeeAmount::Flat(ESCROW_AMOUNT + 1), policy: FeePolicy::Loser }),
+    );
+    assert_eq!(result.unwrap_err(), Ok(ContractError::InvalidAmount));
+    
+    let result = test.contract.try_create_escrow(
+        &test.buyer,
+        &test.seller,
+        &test.arbitrator,
+        &test.token.address,
+        &ESCROW_AMOUNT,
+        &test.description,
+        &Some(ArbitratorFee { amount: FeeAmount::Bps(BPS_DENOMINATOR + 1), policy: FeePolicy::Loser }),
+    );
+    assert_eq!(result.unwrap_err(), Ok(ContractError::InvalidInput));
+}
+
 #[test]
 fn test_arbitrate_non_arbitrator() {
     let (test, escrow_id) = EscrowTest::setup_with_funded_escrow();
@@ -390,7 +614,7 @@ fn test_arbitrate_non_arbitrator() {
     let dispute_reason = String::from_str(&test.env, "Dispute reason");
     test.contract.raise_dispute(&escrow_id, &test.buyer, &dispute_reason);
     
-    let result = test.contract.try_arbitrate(&escrow_id, &test.buyer, &true);
+    let result = test.contract.try_arbitrate(&escrow_id, &test.buyer, &SellerShare::Bps(BPS_DENOMINATOR));
     assert!(result.is_err());
     assert_eq!(result.unwrap_err(), Ok(ContractError::ArbitratorOnly));
 }
@@ -399,7 +623,7 @@ fn test_arbitrate_non_arbitrator() {
 fn test_arbitrate_not_disputed() {
     let (test, escrow_id) = EscrowTest::setup_with_funded_escrow();
     
-    let result = test.contract.try_arbitrate(&escrow_id, &test.arbitrator, &true);
+    let result = test.contract.try_arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(BPS_DENOMINATOR));
     assert!(result.is_err());
     assert_eq!(result.unwrap_err(), Ok(ContractError::EscrowNotDisputed));
 }
@@ -498,10 +722,10 @@ fn test_multiple_arbitration_attempts() {
     let dispute_reason = String::from_str(&test.env, "Dispute reason");
     test.contract.raise_dispute(&escrow_id, &test.buyer, &dispute_reason);
     
-    test.contract.arbitrate(&escrow_id, &test.arbitrator, &true);
+    test.contract.arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(BPS_DENOMINATOR));
     
     // Try to arbitrate again
-    let result = test.contract.try_arbitrate(&escrow_id, &test.arbitrator, &false);
+    let result = test.contract.try_arbitrate(&escrow_id, &test.arbitrator, &SellerShare::Bps(0));
     assert!(result.is_err());
     assert_eq!(result.unwrap_err(), Ok(ContractError::EscrowNotDisputed));
 }
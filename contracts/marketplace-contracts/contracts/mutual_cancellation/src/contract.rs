@@ -1,22 +1,55 @@
+use crate::error::CancellationError;
 use crate::events::Events;
-use crate::storage_types::{CancellationStatus, DataKey, Transaction, INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD, TRANSACTION_BUMP_AMOUNT, TRANSACTION_LIFETIME_THRESHOLD};
-use soroban_sdk::{contract, contractimpl, token, vec, Address, Env, Vec};
+use crate::storage_types::{CancellationStatus, DataKey, EscalationPolicy, LegacyTransaction, LineItem, NegotiationAction, NegotiationEntry, PenaltyPolicy, PendingProposal, Ruling, Transaction, TransactionPage, WindowBounds, BPS_DENOMINATOR, INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD, MAX_LINE_ITEMS, MAX_MIGRATION_BATCH, MAX_NEGOTIATION_HISTORY, MAX_PAGE_SIZE, TRANSACTION_BUMP_AMOUNT, TRANSACTION_LIFETIME_THRESHOLD};
+use soroban_sdk::{contract, contractimpl, token, vec, Address, BytesN, Env, Vec};
 
 #[contract]
 pub struct MutualCancellation;
 
 // Helper functions for storage operations
+fn extend_persistent_ttl(env: &Env, key: &DataKey) {
+    env.storage()
+        .persistent()
+        .extend_ttl(key, TRANSACTION_LIFETIME_THRESHOLD, TRANSACTION_BUMP_AMOUNT);
+}
+
+fn extend_instance_ttl(env: &Env) {
+    env.storage()
+        .instance()
+        .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+}
+
 fn get_transaction(env: &Env, id: u64) -> Option<Transaction> {
     let key = DataKey::Transaction(id);
-    env.storage().instance().get(&key)
+    let transaction = env.storage().persistent().get(&key);
+    if transaction.is_some() {
+        extend_persistent_ttl(env, &key);
+    }
+    transaction
 }
 
 fn save_transaction(env: &Env, transaction: &Transaction) {
     let key = DataKey::Transaction(transaction.id);
-    env.storage().instance().set(&key, transaction);
-    env.storage()
-        .instance()
-        .extend_ttl(TRANSACTION_LIFETIME_THRESHOLD, TRANSACTION_BUMP_AMOUNT);
+    
+    // Move the transaction between the per-party status counters if its status changed, and
+    // update the proposals indexes if its pending proposal changed
+    let previous = env.storage().persistent().get::<DataKey, Transaction>(&key);
+    let previous_status = previous.as_ref().map(|previous| previous.status);
+    if previous_status != Some(transaction.status) {
+        if let Some(status) = previous_status {
+            update_status_counts(env, transaction, status, false);
+        }
+        update_status_counts(env, transaction, transaction.status, true);
+    }
+    let previous_proposal = previous.as_ref().and_then(pending_proposal);
+    let proposal = pending_proposal(transaction);
+    if previous_proposal != proposal {
+        update_pending_proposals(env, transaction, proposal);
+    }
+    
+    env.storage().persistent().set(&key, transaction);
+    extend_persistent_ttl(env, &key);
+    extend_instance_ttl(env);
 }
 
 fn get_transaction_counter(env: &Env) -> u64 {
@@ -34,71 +67,655 @@ fn increment_transaction_counter(env: &Env) -> u64 {
     counter
 }
 
+// Get a list of transaction IDs stored under `key`
+fn get_transaction_ids(env: &Env, key: &DataKey) -> Vec<u64> {
+    match env.storage().persistent().get(key) {
+        Some(transactions) => {
+            extend_persistent_ttl(env, key);
+            transactions
+        }
+        None => vec![env],
+    }
+}
+
+fn add_transaction_id(env: &Env, key: &DataKey, transaction_id: u64) {
+    let mut transactions = get_transaction_ids(env, key);
+    transactions.push_back(transaction_id);
+    env.storage().persistent().set(key, &transactions);
+    extend_persistent_ttl(env, key);
+}
+
 fn get_buyer_transactions(env: &Env, buyer: &Address) -> Vec<u64> {
+    get_transaction_ids(env, &DataKey::BuyerTransactions(buyer.clone()))
+}
+
+fn add_buyer_transaction(env: &Env, buyer: &Address, transaction_id: u64) {
+    add_transaction_id(env, &DataKey::BuyerTransactions(buyer.clone()), transaction_id);
+}
+
+fn get_seller_transactions(env: &Env, seller: &Address) -> Vec<u64> {
+    get_transaction_ids(env, &DataKey::SellerTransactions(seller.clone()))
+}
+
+fn add_seller_transaction(env: &Env, seller: &Address, transaction_id: u64) {
+    add_transaction_id(env, &DataKey::SellerTransactions(seller.clone()), transaction_id);
+}
+
+fn get_status_count(env: &Env, key: &DataKey) -> u32 {
+    match env.storage().persistent().get(key) {
+        Some(count) => {
+            extend_persistent_ttl(env, key);
+            count
+        }
+        None => 0,
+    }
+}
+
+// Count a transaction in, or out of, the buyer's and seller's counters for `status`. Pending
+// proposals are counted from the proposals indexes instead, which know when they expire.
+fn update_status_counts(env: &Env, transaction: &Transaction, status: CancellationStatus, added: bool) {
+    if is_proposed(status) {
+        return;
+    }
+    let keys = [
+        DataKey::BuyerStatusCount(transaction.buyer.clone(), status),
+        DataKey::SellerStatusCount(transaction.seller.clone(), status),
+    ];
+    for key in keys.iter() {
+        let count = get_status_count(env, key);
+        let count = if added { count + 1 } else { count.saturating_sub(1) };
+        env.storage().persistent().set(key, &count);
+        extend_persistent_ttl(env, key);
+    }
+}
+
+fn is_proposed(status: CancellationStatus) -> bool {
+    matches!(status, CancellationStatus::ProposedByBuyer | CancellationStatus::ProposedBySeller)
+}
+
+// Get the entry of a transaction's pending proposal in the proposals indexes, if it has one
+fn pending_proposal(transaction: &Transaction) -> Option<PendingProposal> {
+    if is_proposed(transaction.status) {
+        Some(PendingProposal {
+            id: transaction.id,
+            status: transaction.status,
+            deadline: proposal_deadline(transaction),
+        })
+    } else {
+        None
+    }
+}
+
+fn get_pending_proposals(env: &Env, key: &DataKey) -> Vec<PendingProposal> {
+    match env.storage().persistent().get(key) {
+        Some(proposals) => {
+            extend_persistent_ttl(env, key);
+            proposals
+        }
+        None => vec![env],
+    }
+}
+
+// Replace the transaction's entry in the buyer's and seller's proposals indexes with `proposal`,
+// or drop it if the transaction no longer has a pending proposal
+fn update_pending_proposals(env: &Env, transaction: &Transaction, proposal: Option<PendingProposal>) {
+    let keys = [
+        DataKey::BuyerProposals(transaction.buyer.clone()),
+        DataKey::SellerProposals(transaction.seller.clone()),
+    ];
+    for key in keys.iter() {
+        let mut proposals = get_pending_proposals(env, key);
+        if let Some(index) = proposals.iter().position(|entry| entry.id == transaction.id) {
+            proposals.remove(index as u32);
+        }
+        if let Some(proposal) = proposal.clone() {
+            proposals.push_back(proposal);
+        }
+        
+        if proposals.is_empty() {
+            env.storage().persistent().remove(key);
+        } else {
+            env.storage().persistent().set(key, &proposals);
+            extend_persistent_ttl(env, key);
+        }
+    }
+}
+
+// Get the number of a party's transactions with this effective status. Pending and expired
+// proposals are counted from the party's proposals index, other statuses from their counters.
+fn get_effective_status_count(
+    env: &Env,
+    count_key: &DataKey,
+    proposals_key: &DataKey,
+    status: CancellationStatus,
+) -> u32 {
+    match status {
+        CancellationStatus::ProposedByBuyer | CancellationStatus::ProposedBySeller | CancellationStatus::Expired => {
+            let now = env.ledger().timestamp();
+            get_pending_proposals(env, proposals_key)
+                .iter()
+                .filter(|proposal| match status {
+                    CancellationStatus::Expired => now > proposal.deadline,
+                    _ => proposal.status == status && now <= proposal.deadline,
+                })
+                .count() as u32
+        }
+        _ => get_status_count(env, count_key),
+    }
+}
+
+// Get up to `limit` transactions, at most `MAX_PAGE_SIZE`, from position `offset` of a party's
+// transaction IDs, keeping those whose effective status matches `status`, if given. Reading stops
+// once the page is full or `MAX_PAGE_SIZE` transactions were read, and the page reports the
+// position to continue from.
+fn get_transactions_page(
+    env: &Env,
+    transaction_ids: &Vec<u64>,
+    status: Option<CancellationStatus>,
+    offset: u32,
+    limit: u32,
+) -> TransactionPage {
+    let mut transactions = vec![env];
+    let limit = limit.min(MAX_PAGE_SIZE);
+    let end = offset.saturating_add(MAX_PAGE_SIZE).min(transaction_ids.len());
+    
+    let mut next_offset = offset;
+    while next_offset < end && transactions.len() < limit {
+        if let Some(tx) = get_transaction(env, transaction_ids.get_unchecked(next_offset)) {
+            if status.is_none() || status == Some(effective_status(env, &tx)) {
+                transactions.push_back(tx);
+            }
+        }
+        next_offset += 1;
+    }
+    
+    TransactionPage { transactions, next_offset }
+}
+
+fn get_negotiation_history(env: &Env, transaction_id: u64) -> Vec<NegotiationEntry> {
+    let key = DataKey::NegotiationHistory(transaction_id);
+    match env.storage().persistent().get(&key) {
+        Some(history) => {
+            extend_persistent_ttl(env, &key);
+            history
+        }
+        None => vec![env],
+    }
+}
+
+// Move a per-party index kept in instance storage to persistent storage, ahead of any IDs
+// added to the persistent index since the upgrade
+fn move_index_to_persistent(env: &Env, key: &DataKey) {
+    if let Some(mut transactions) = env.storage().instance().get::<DataKey, Vec<u64>>(key) {
+        transactions.append(&get_transaction_ids(env, key));
+        env.storage().persistent().set(key, &transactions);
+        extend_persistent_ttl(env, key);
+        env.storage().instance().remove(key);
+    }
+}
+
+// Append a negotiation step, dropping the oldest one once the history is full
+fn add_negotiation_entry(env: &Env, transaction: &Transaction, action: NegotiationAction, party: &Address) {
+    let mut history = get_negotiation_history(env, transaction.id);
+    if history.len() >= MAX_NEGOTIATION_HISTORY {
+        history.pop_front();
+    }
+    history.push_back(NegotiationEntry {
+        action,
+        party: party.clone(),
+        refund_amount: transaction.refund_amount,
+        items: transaction.proposed_items.clone(),
+        timestamp: env.ledger().timestamp(),
+    });
+    let key = DataKey::NegotiationHistory(transaction.id);
+    env.storage().persistent().set(&key, &history);
+    extend_persistent_ttl(env, &key);
+}
+
+// Get the party who made the pending cancellation proposal
+fn get_proposer(transaction: &Transaction) -> Address {
+    match transaction.status {
+        CancellationStatus::ProposedByBuyer => transaction.buyer.clone(),
+        CancellationStatus::ProposedBySeller => transaction.seller.clone(),
+        _ => panic!("No cancellation proposal"),
+    }
+}
+
+fn get_response_window(env: &Env) -> u64 {
     env.storage()
         .instance()
-        .get(&DataKey::BuyerTransactions(buyer.clone()))
-        .unwrap_or_else(|| vec![env])
+        .get(&DataKey::ResponseWindow)
+        .unwrap_or(7 * 24 * 60 * 60) // Default: 7 days in seconds
 }
 
-fn add_buyer_transaction(env: &Env, buyer: &Address, transaction_id: u64) {
-    let mut transactions = get_buyer_transactions(env, buyer);
-    transactions.push_back(transaction_id);
+fn get_response_window_bounds(env: &Env) -> WindowBounds {
     env.storage()
         .instance()
-        .set(&DataKey::BuyerTransactions(buyer.clone()), &transactions);
+        .get(&DataKey::ResponseWindowBounds)
+        .unwrap_or(WindowBounds { min: 0, max: u64::MAX }) // Default: unbounded
 }
 
-fn get_seller_transactions(env: &Env, seller: &Address) -> Vec<u64> {
+fn get_admin(env: &Env) -> Result<Address, CancellationError> {
     env.storage()
         .instance()
-        .get(&DataKey::SellerTransactions(seller.clone()))
-        .unwrap_or_else(|| vec![env])
+        .get(&DataKey::Admin)
+        .ok_or(CancellationError::NotInitialized)
 }
 
-fn add_seller_transaction(env: &Env, seller: &Address, transaction_id: u64) {
-    let mut transactions = get_seller_transactions(env, seller);
-    transactions.push_back(transaction_id);
+// Get the transaction ID the storage migration continues from
+fn get_migration_cursor(env: &Env) -> u64 {
     env.storage()
         .instance()
-        .set(&DataKey::SellerTransactions(seller.clone()), &transactions);
+        .get(&DataKey::MigrationCursor)
+        .unwrap_or(1)
 }
 
-fn get_response_window(env: &Env) -> u64 {
+fn get_auto_release_period(env: &Env) -> u64 {
     env.storage()
         .instance()
-        .get(&DataKey::ResponseWindow)
-        .unwrap_or(7 * 24 * 60 * 60) // Default: 7 days in seconds
+        .get(&DataKey::AutoReleasePeriod)
+        .unwrap_or(0) // Default: auto-release disabled
+}
+
+fn get_arbiter(env: &Env, transaction_id: u64) -> Option<Address> {
+    let key = DataKey::Arbiter(transaction_id);
+    let arbiter = env.storage().persistent().get(&key);
+    if arbiter.is_some() {
+        extend_persistent_ttl(env, &key);
+    }
+    arbiter
+}
+
+fn get_default_arbiter(env: &Env) -> Option<Address> {
+    env.storage()
+        .instance()
+        .get(&DataKey::DefaultArbiter)
+}
+
+fn get_escalation_policy(env: &Env) -> EscalationPolicy {
+    env.storage()
+        .instance()
+        .get(&DataKey::EscalationPolicy)
+        .unwrap_or(EscalationPolicy { max_expired_proposals: 0, max_age: 0 }) // Default: escalation disabled
+}
+
+// Get the last timestamp at which the pending cancellation proposal can be answered. A
+// response window too long to end before the largest timestamp never ends.
+fn proposal_deadline(transaction: &Transaction) -> u64 {
+    transaction.proposal_timestamp.saturating_add(transaction.response_window)
+}
+
+// Check if the response window of the pending cancellation proposal has passed
+fn is_proposal_expired(env: &Env, transaction: &Transaction) -> bool {
+    match transaction.status {
+        CancellationStatus::ProposedByBuyer | CancellationStatus::ProposedBySeller => {
+            env.ledger().timestamp() > proposal_deadline(transaction)
+        }
+        _ => false,
+    }
+}
+
+fn get_penalty_policy(env: &Env) -> PenaltyPolicy {
+    env.storage()
+        .instance()
+        .get(&DataKey::PenaltyPolicy)
+        .unwrap_or(PenaltyPolicy { shipped_fee_bps: 0, seller_compensation_bps: 0 }) // Default: no penalties
+}
+
+// Get the compensation the seller would owe from their bond in `token` if the counterparty agreed
+// to every pending proposal on the seller's transactions that the seller started
+fn get_reserved_bond(env: &Env, seller: &Address, token: &Address) -> i128 {
+    let policy = get_penalty_policy(env);
+    let now = env.ledger().timestamp();
+    let mut reserved = 0;
+    for proposal in get_pending_proposals(env, &DataKey::SellerProposals(seller.clone())).iter() {
+        if now > proposal.deadline {
+            continue;
+        }
+        if let Some(transaction) = get_transaction(env, proposal.id) {
+            if transaction.initiator == CancellationStatus::ProposedBySeller && transaction.token == *token {
+                let cancelled_amount = transaction.items_amount(&transaction.proposed_items);
+                reserved += bps_of(cancelled_amount, policy.seller_compensation_bps);
+            }
+        }
+    }
+    reserved
+}
+
+fn get_seller_bond(env: &Env, seller: &Address, token: &Address) -> i128 {
+    let key = DataKey::SellerBond(seller.clone(), token.clone());
+    match env.storage().persistent().get(&key) {
+        Some(bond) => {
+            extend_persistent_ttl(env, &key);
+            bond
+        }
+        None => 0,
+    }
+}
+
+fn set_seller_bond(env: &Env, seller: &Address, token: &Address, bond: i128) {
+    let key = DataKey::SellerBond(seller.clone(), token.clone());
+    env.storage().persistent().set(&key, &bond);
+    extend_persistent_ttl(env, &key);
+    
+    Events::new(env).bond_updated(seller.clone(), token.clone(), bond);
+}
+
+// Part of `amount` given by a rate in basis points
+fn bps_of(amount: i128, bps: u32) -> i128 {
+    amount * bps as i128 / BPS_DENOMINATOR as i128
+}
+
+// Get the status of a transaction as of the current ledger time
+fn effective_status(env: &Env, transaction: &Transaction) -> CancellationStatus {
+    if is_proposal_expired(env, transaction) {
+        CancellationStatus::Expired
+    } else {
+        transaction.status
+    }
+}
+
+// Get the line items a proposal cancels, all items not cancelled yet if none are given.
+// Every given ID must name a distinct item that is not cancelled.
+fn resolve_items(env: &Env, transaction: &Transaction, item_ids: &Vec<u64>) -> Result<Vec<u64>, CancellationError> {
+    let mut resolved = vec![env];
+    for item in transaction.items.iter() {
+        if !item.cancelled && (item_ids.is_empty() || item_ids.contains(item.id)) {
+            resolved.push_back(item.id);
+        }
+    }
+    if !item_ids.is_empty() && resolved.len() != item_ids.len() {
+        return Err(CancellationError::InvalidItems);
+    }
+    Ok(resolved)
+}
+
+// Pay escrowed funds out to the buyer and the seller
+fn pay_out(env: &Env, transaction: &Transaction, buyer_amount: i128, seller_amount: i128) {
+    let token_client = token::Client::new(env, &transaction.token);
+    if buyer_amount > 0 {
+        token_client.transfer(
+            &env.current_contract_address(),
+            &transaction.buyer,
+            &buyer_amount,
+        );
+    }
+    if seller_amount > 0 {
+        token_client.transfer(
+            &env.current_contract_address(),
+            &transaction.seller,
+            &seller_amount,
+        );
+    }
+}
+
+// Mark the items of the pending or agreed cancellation as cancelled and take their amount out of escrow
+fn cancel_items(env: &Env, transaction: &mut Transaction) {
+    let mut items = vec![env];
+    for mut item in transaction.items.iter() {
+        if transaction.proposed_items.contains(item.id) {
+            item.cancelled = true;
+        }
+        items.push_back(item);
+    }
+    transaction.items = items;
+    transaction.amount -= transaction.items_amount(&transaction.proposed_items);
+}
+
+// Transfer the escrowed funds to the seller and mark the transaction as released
+fn release_funds(env: &Env, transaction: &mut Transaction, auto_released: bool) {
+    token::Client::new(env, &transaction.token).transfer(
+        &env.current_contract_address(),
+        &transaction.seller,
+        &transaction.amount,
+    );
+
+    transaction.status = CancellationStatus::Released;
+    save_transaction(env, transaction);
+
+    Events::new(env).funds_released(transaction, auto_released);
 }
 
 #[contractimpl]
 impl MutualCancellation {
-    // Initialize the contract with a default response window for cancellation proposals
-    pub fn initialize(env: Env, response_window: u64) {
+    // Initialize the contract with an admin, a default response window for cancellation proposals
+    // within `window_bounds`, and a default auto-release period for new transactions (0 disables
+    // auto-release). Can only be called once.
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        response_window: u64,
+        window_bounds: WindowBounds,
+        auto_release_period: u64,
+    ) -> Result<(), CancellationError> {
+        // Check if the contract is already initialized
+        if env.storage().instance().has(&DataKey::Admin) {
+            return Err(CancellationError::AlreadyInitialized);
+        }
+        
+        // Require admin auth
+        admin.require_auth();
+        
+        // Validate the response window
+        if window_bounds.min > window_bounds.max || !window_bounds.contains(response_window) {
+            return Err(CancellationError::WindowOutOfBounds);
+        }
+        
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage()
+            .instance()
+            .set(&DataKey::ResponseWindowBounds, &window_bounds);
         env.storage()
             .instance()
             .set(&DataKey::ResponseWindow, &response_window);
+        env.storage()
+            .instance()
+            .set(&DataKey::AutoReleasePeriod, &auto_release_period);
+        
+        extend_instance_ttl(&env);
+        
+        Ok(())
+    }
+
+    // Admin changes the default response window for new transactions, within the configured bounds
+    pub fn set_response_window(env: Env, response_window: u64) -> Result<(), CancellationError> {
+        // Require admin auth
+        let admin = get_admin(&env)?;
+        admin.require_auth();
+        
+        // Validate the response window
+        if !get_response_window_bounds(&env).contains(response_window) {
+            return Err(CancellationError::WindowOutOfBounds);
+        }
         
         env.storage()
             .instance()
-            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+            .set(&DataKey::ResponseWindow, &response_window);
+        extend_instance_ttl(&env);
+        
+        // Emit event
+        Events::new(&env).response_window_updated(admin, response_window);
+        
+        Ok(())
     }
 
-    // Create a new transaction in escrow
+    // Admin changes the allowed range for response windows. The current default must stay within it.
+    pub fn set_response_window_bounds(env: Env, window_bounds: WindowBounds) -> Result<(), CancellationError> {
+        // Require admin auth
+        get_admin(&env)?.require_auth();
+        
+        // Validate the bounds
+        if window_bounds.min > window_bounds.max || !window_bounds.contains(get_response_window(&env)) {
+            return Err(CancellationError::WindowOutOfBounds);
+        }
+        
+        env.storage()
+            .instance()
+            .set(&DataKey::ResponseWindowBounds, &window_bounds);
+        extend_instance_ttl(&env);
+        
+        Ok(())
+    }
+
+    // Admin sets the arbiter for transactions created without one, or clears it
+    pub fn set_default_arbiter(env: Env, arbiter: Option<Address>) -> Result<(), CancellationError> {
+        // Require admin auth
+        get_admin(&env)?.require_auth();
+        
+        match arbiter {
+            Some(arbiter) => env.storage().instance().set(&DataKey::DefaultArbiter, &arbiter),
+            None => env.storage().instance().remove(&DataKey::DefaultArbiter),
+        }
+        extend_instance_ttl(&env);
+        
+        Ok(())
+    }
+
+    // Admin sets after how many expired proposals, or how long after creation, a transaction
+    // can be escalated to its arbiter
+    pub fn set_escalation_policy(env: Env, policy: EscalationPolicy) -> Result<(), CancellationError> {
+        // Require admin auth
+        get_admin(&env)?.require_auth();
+        
+        env.storage()
+            .instance()
+            .set(&DataKey::EscalationPolicy, &policy);
+        extend_instance_ttl(&env);
+        
+        Ok(())
+    }
+
+    // Admin sets the penalties applied to agreed cancellations
+    pub fn set_penalty_policy(env: Env, policy: PenaltyPolicy) -> Result<(), CancellationError> {
+        // Require admin auth
+        get_admin(&env)?.require_auth();
+        
+        // Validate the rates
+        if policy.shipped_fee_bps > BPS_DENOMINATOR || policy.seller_compensation_bps > BPS_DENOMINATOR {
+            return Err(CancellationError::InvalidPolicy);
+        }
+        
+        env.storage()
+            .instance()
+            .set(&DataKey::PenaltyPolicy, &policy);
+        extend_instance_ttl(&env);
+        
+        Ok(())
+    }
+
+    // Admin replaces the contract code, keeping its storage. Run `migrate_storage` afterwards
+    // when upgrading a deployment that still keeps its records in instance storage.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), CancellationError> {
+        // Require admin auth
+        get_admin(&env)?.require_auth();
+        
+        env.deployer().update_current_contract_wasm(new_wasm_hash);
+        
+        Ok(())
+    }
+
+    // Move transactions and per-party indexes that the first version of the contract kept in
+    // instance storage to persistent storage, converting them to the current layout. Walks at most
+    // `limit` transaction IDs from `start_id`, up to `MAX_MIGRATION_BATCH`, and returns the ID to
+    // continue from, which is also kept as the migration cursor. Migrated IDs are skipped, so a
+    // batch can safely be run again.
+    pub fn migrate_storage(env: Env, start_id: u64, limit: u32) -> Result<u64, CancellationError> {
+        // Require admin auth
+        get_admin(&env)?.require_auth();
+        
+        let last_id = get_transaction_counter(&env);
+        let start_id = start_id.max(1);
+        if start_id > last_id {
+            return Err(CancellationError::AlreadyMigrated);
+        }
+        let end_id = start_id
+            .saturating_add(limit.min(MAX_MIGRATION_BATCH) as u64)
+            .min(last_id + 1);
+        
+        for id in start_id..end_id {
+            let key = DataKey::Transaction(id);
+            if let Some(legacy) = env.storage().instance().get::<DataKey, LegacyTransaction>(&key) {
+                let transaction = legacy.into_transaction(&env, env.ledger().timestamp());
+                env.storage().instance().remove(&key);
+                // Saving counts the transaction in the status counters, as it has no previous status
+                save_transaction(&env, &transaction);
+                move_index_to_persistent(&env, &DataKey::BuyerTransactions(transaction.buyer.clone()));
+                move_index_to_persistent(&env, &DataKey::SellerTransactions(transaction.seller.clone()));
+            }
+        }
+        
+        if end_id > get_migration_cursor(&env) {
+            env.storage()
+                .instance()
+                .set(&DataKey::MigrationCursor, &end_id);
+        }
+        extend_instance_ttl(&env);
+        
+        Ok(end_id)
+    }
+
+    // Get the transaction ID the storage migration continues from
+    pub fn get_migration_cursor(env: Env) -> u64 {
+        get_migration_cursor(&env)
+    }
+
+    // Create a new transaction in escrow. The buyer can choose a response window for
+    // cancellation proposals within the configured bounds, and an arbiter for escalations,
+    // or use the default ones. An arbiter other than the default one needs the seller's
+    // authorization too, and neither party can be the arbiter.
     pub fn create_transaction(
         env: Env,
         buyer: Address,
         seller: Address,
         token: Address,
-        amount: i128,
-    ) -> u64 {
+        items: Vec<(u64, i128)>,
+        response_window: Option<u64>,
+        arbiter: Option<Address>,
+    ) -> Result<u64, CancellationError> {
+        // The response window defaults and bounds are only set by `initialize`
+        get_admin(&env)?;
+        
         // Validate inputs
-        if amount <= 0 {
-            panic!("Amount must be positive");
+        if items.is_empty() || items.len() > MAX_LINE_ITEMS {
+            return Err(CancellationError::InvalidItems);
+        }
+        let mut line_items: Vec<LineItem> = vec![&env];
+        let mut amount: i128 = 0;
+        for (item_id, item_amount) in items.iter() {
+            if item_amount <= 0 {
+                return Err(CancellationError::InvalidAmount);
+            }
+            if line_items.iter().any(|item| item.id == item_id) {
+                return Err(CancellationError::InvalidItems);
+            }
+            line_items.push_back(LineItem {
+                id: item_id,
+                amount: item_amount,
+                cancelled: false,
+            });
+            amount += item_amount;
+        }
+        let response_window = match response_window {
+            Some(response_window) => {
+                if !get_response_window_bounds(&env).contains(response_window) {
+                    return Err(CancellationError::WindowOutOfBounds);
+                }
+                response_window
+            }
+            None => get_response_window(&env),
+        };
+        let custom_arbiter = arbiter.is_some();
+        let arbiter = arbiter.or_else(|| get_default_arbiter(&env));
+        if arbiter.as_ref().is_some_and(|arbiter| *arbiter == buyer || *arbiter == seller) {
+            return Err(CancellationError::InvalidArbiter);
         }
 
-        // Authenticate the buyer who is creating the transaction
+        // Authenticate the buyer who is creating the transaction, and the seller if the buyer
+        // chose the arbiter, as the arbiter decides on their funds
         buyer.require_auth();
+        if custom_arbiter {
+            seller.require_auth();
+        }
 
         // Transfer funds from buyer to the contract
         token::Client::new(&env, &token).transfer(
@@ -117,20 +734,33 @@ impl MutualCancellation {
             seller: seller.clone(),
             token,
             amount,
+            items: line_items,
             status: CancellationStatus::None,
             proposal_timestamp: 0,
-            response_window: get_response_window(&env),
+            response_window,
+            created_timestamp: env.ledger().timestamp(),
+            auto_release_period: get_auto_release_period(&env),
+            refund_amount: 0,
+            proposed_items: vec![&env],
+            expired_proposals: 0,
+            shipped: false,
+            initiator: CancellationStatus::None,
         };
 
         // Store the transaction
         save_transaction(&env, &transaction);
+        if let Some(arbiter) = arbiter {
+            let key = DataKey::Arbiter(id);
+            env.storage().persistent().set(&key, &arbiter);
+            extend_persistent_ttl(&env, &key);
+        }
         add_buyer_transaction(&env, &buyer, id);
         add_seller_transaction(&env, &seller, id);
 
         // Emit event
         Events::new(&env).transaction_created(&transaction);
 
-        id
+        Ok(id)
     }
 
     // Get details of a specific transaction
@@ -138,231 +768,799 @@ impl MutualCancellation {
         get_transaction(&env, id)
     }
 
-    // Get all transactions associated with a buyer
-    pub fn get_buyer_transactions(env: Env, buyer: Address) -> Vec<Transaction> {
+    // Get a page of the transactions associated with a buyer, oldest first, starting at position
+    // `offset` of the buyer's transactions. `status` keeps only the transactions with this
+    // effective status. A page holds at most `limit` transactions and reads at most
+    // `MAX_PAGE_SIZE`, so a filtered page can come back short; callers continue from its
+    // `next_offset` until it reaches the number of transactions.
+    pub fn get_buyer_transactions(
+        env: Env,
+        buyer: Address,
+        status: Option<CancellationStatus>,
+        offset: u32,
+        limit: u32,
+    ) -> TransactionPage {
         let transaction_ids = get_buyer_transactions(&env, &buyer);
-        let mut transactions = vec![&env];
-        
-        for id in transaction_ids.iter() {
-            if let Some(tx) = get_transaction(&env, id) {
-                transactions.push_back(tx);
-            }
-        }
-        
-        transactions
+        get_transactions_page(&env, &transaction_ids, status, offset, limit)
     }
 
-    // Get all transactions associated with a seller
-    pub fn get_seller_transactions(env: Env, seller: Address) -> Vec<Transaction> {
+    // Get a page of the transactions associated with a seller, see `get_buyer_transactions`
+    pub fn get_seller_transactions(
+        env: Env,
+        seller: Address,
+        status: Option<CancellationStatus>,
+        offset: u32,
+        limit: u32,
+    ) -> TransactionPage {
         let transaction_ids = get_seller_transactions(&env, &seller);
-        let mut transactions = vec![&env];
-        
-        for id in transaction_ids.iter() {
-            if let Some(tx) = get_transaction(&env, id) {
-                transactions.push_back(tx);
-            }
-        }
-        
-        transactions
+        get_transactions_page(&env, &transaction_ids, status, offset, limit)
     }
 
-    // Buyer proposes cancellation of a transaction
-    pub fn buyer_propose_cancellation(env: Env, id: u64) {
+    // Get the number of a buyer's transactions with this effective status. Proposals whose
+    // response window has passed are counted under `Expired`, not as pending.
+    pub fn get_buyer_status_count(env: Env, buyer: Address, status: CancellationStatus) -> u32 {
+        get_effective_status_count(
+            &env,
+            &DataKey::BuyerStatusCount(buyer.clone(), status),
+            &DataKey::BuyerProposals(buyer),
+            status,
+        )
+    }
+
+    // Get the number of a seller's transactions with this effective status, see
+    // `get_buyer_status_count`
+    pub fn get_seller_status_count(env: Env, seller: Address, status: CancellationStatus) -> u32 {
+        get_effective_status_count(
+            &env,
+            &DataKey::SellerStatusCount(seller.clone(), status),
+            &DataKey::SellerProposals(seller),
+            status,
+        )
+    }
+
+    // Buyer proposes cancellation of the line items `item_ids` of a transaction, or of all
+    // remaining items if none are given, refunding `refund_amount` to the buyer and paying
+    // the rest of the items' amount to the seller
+    pub fn buyer_propose_cancellation(env: Env, id: u64, item_ids: Vec<u64>, refund_amount: i128) -> Result<(), CancellationError> {
         // Ensure the transaction exists
         let transaction_opt = get_transaction(&env, id);
         if transaction_opt.is_none() {
-            panic!("Transaction not found");
+            return Err(CancellationError::TransactionNotFound);
         }
         
         let mut transaction = transaction_opt.unwrap();
         
         // Check if cancellation is already completed
         if transaction.status == CancellationStatus::Completed {
-            panic!("Transaction already cancelled");
+            return Err(CancellationError::AlreadyCancelled);
+        }
+        
+        // Check if funds were already released to the seller
+        if transaction.status == CancellationStatus::Released {
+            return Err(CancellationError::AlreadyReleased);
+        }
+        
+        // Check if the transaction is waiting for the arbiter's ruling
+        if transaction.status == CancellationStatus::Escalated {
+            return Err(CancellationError::AlreadyEscalated);
         }
         
         // Check if there's already a proposal in place
         if transaction.status != CancellationStatus::None {
             // Check if the proposal has expired
             let current_time = env.ledger().timestamp();
-            if current_time > transaction.proposal_timestamp + transaction.response_window {
+            if current_time > proposal_deadline(&transaction) {
                 // Reset expired proposal
+                add_negotiation_entry(&env, &transaction, NegotiationAction::Expired, &get_proposer(&transaction));
                 transaction.status = CancellationStatus::None;
+                transaction.initiator = CancellationStatus::None;
+                transaction.expired_proposals += 1;
             } else {
-                panic!("Cancellation already proposed");
+                return Err(CancellationError::AlreadyProposed);
             }
         }
         
         // Require buyer auth
         transaction.buyer.require_auth();
+        
+        // Validate the proposed items and refund
+        let proposed_items = resolve_items(&env, &transaction, &item_ids)?;
+        if refund_amount <= 0 || refund_amount > transaction.items_amount(&proposed_items) {
+            return Err(CancellationError::InvalidRefundAmount);
+        }
                 
         // Update transaction status
         transaction.status = CancellationStatus::ProposedByBuyer;
+        transaction.initiator = CancellationStatus::ProposedByBuyer;
         transaction.proposal_timestamp = env.ledger().timestamp();
+        transaction.refund_amount = refund_amount;
+        transaction.proposed_items = proposed_items;
         
         // Save updated transaction
         save_transaction(&env, &transaction);
+        add_negotiation_entry(&env, &transaction, NegotiationAction::Proposed, &get_proposer(&transaction));
         
         // Emit event
         Events::new(&env).cancellation_proposed(&transaction);
+        
+        Ok(())
     }
 
-    // Seller proposes cancellation of a transaction
-    pub fn seller_propose_cancellation(env: Env, id: u64) {
+    // Seller proposes cancellation of the line items `item_ids` of a transaction, or of all
+    // remaining items if none are given, refunding `refund_amount` to the buyer and keeping
+    // the rest of the items' amount
+    pub fn seller_propose_cancellation(env: Env, id: u64, item_ids: Vec<u64>, refund_amount: i128) -> Result<(), CancellationError> {
         // Ensure the transaction exists
         let transaction_opt = get_transaction(&env, id);
         if transaction_opt.is_none() {
-            panic!("Transaction not found");
+            return Err(CancellationError::TransactionNotFound);
         }
         
         let mut transaction = transaction_opt.unwrap();
         
         // Check if cancellation is already completed
         if transaction.status == CancellationStatus::Completed {
-            panic!("Transaction already cancelled");
+            return Err(CancellationError::AlreadyCancelled);
+        }
+        
+        // Check if funds were already released to the seller
+        if transaction.status == CancellationStatus::Released {
+            return Err(CancellationError::AlreadyReleased);
+        }
+        
+        // Check if the transaction is waiting for the arbiter's ruling
+        if transaction.status == CancellationStatus::Escalated {
+            return Err(CancellationError::AlreadyEscalated);
         }
         
         // Check if there's already a proposal in place
         if transaction.status != CancellationStatus::None {
             // Check if the proposal has expired
             let current_time = env.ledger().timestamp();
-            if current_time > transaction.proposal_timestamp + transaction.response_window {
+            if current_time > proposal_deadline(&transaction) {
                 // Reset expired proposal
+                add_negotiation_entry(&env, &transaction, NegotiationAction::Expired, &get_proposer(&transaction));
                 transaction.status = CancellationStatus::None;
+                transaction.initiator = CancellationStatus::None;
+                transaction.expired_proposals += 1;
             } else {
-                panic!("Cancellation already proposed");
+                return Err(CancellationError::AlreadyProposed);
             }
         }
         
         // Require seller auth
         transaction.seller.require_auth();
+        
+        // Validate the proposed items and refund
+        let proposed_items = resolve_items(&env, &transaction, &item_ids)?;
+        if refund_amount <= 0 || refund_amount > transaction.items_amount(&proposed_items) {
+            return Err(CancellationError::InvalidRefundAmount);
+        }
                 
         // Update transaction status
         transaction.status = CancellationStatus::ProposedBySeller;
+        transaction.initiator = CancellationStatus::ProposedBySeller;
         transaction.proposal_timestamp = env.ledger().timestamp();
+        transaction.refund_amount = refund_amount;
+        transaction.proposed_items = proposed_items;
         
         // Save updated transaction
         save_transaction(&env, &transaction);
+        add_negotiation_entry(&env, &transaction, NegotiationAction::Proposed, &get_proposer(&transaction));
         
         // Emit event
         Events::new(&env).cancellation_proposed(&transaction);
+        
+        Ok(())
     }
 
     // Agree to a cancellation proposal
-    pub fn agree_to_cancellation(env: Env, id: u64) {
+    pub fn agree_to_cancellation(env: Env, id: u64) -> Result<(), CancellationError> {
         // Ensure the transaction exists
         let transaction_opt = get_transaction(&env, id);
         if transaction_opt.is_none() {
-            panic!("Transaction not found");
+            return Err(CancellationError::TransactionNotFound);
         }
         
         let mut transaction = transaction_opt.unwrap();
         
         // Check if cancellation is already completed
         if transaction.status == CancellationStatus::Completed {
-            panic!("Transaction already cancelled");
+            return Err(CancellationError::AlreadyCancelled);
+        }
+        
+        // Check if funds were already released to the seller
+        if transaction.status == CancellationStatus::Released {
+            return Err(CancellationError::AlreadyReleased);
+        }
+        
+        // Check if the transaction is waiting for the arbiter's ruling
+        if transaction.status == CancellationStatus::Escalated {
+            return Err(CancellationError::AlreadyEscalated);
         }
         
         // Check if there's a proposal in place
         if transaction.status == CancellationStatus::None {
-            panic!("No cancellation proposal to agree to");
+            return Err(CancellationError::NoProposal);
         }
         
         // Check if the proposal has expired
         let current_time = env.ledger().timestamp();
-        if current_time > transaction.proposal_timestamp + transaction.response_window {
-            // Reset proposal and notify
-            transaction.status = CancellationStatus::None;
-            save_transaction(&env, &transaction);
-            Events::new(&env).cancellation_expired(&transaction);
-            panic!("Cancellation proposal has expired");
+        if current_time > proposal_deadline(&transaction) {
+            return Err(CancellationError::ProposalExpired);
         }
         
         // Verify the caller is the correct counterparty and require their auth
-        match transaction.status {
+        let counterparty = match transaction.status {
+            CancellationStatus::ProposedByBuyer => transaction.seller.clone(),
+            CancellationStatus::ProposedBySeller => transaction.buyer.clone(),
+            _ => panic!("Invalid cancellation status"),
+        };
+        counterparty.require_auth();
+        
+        // Apply the penalty policy to the party who started the cancellation, whoever made the
+        // agreed counter-proposal. A buyer cancelling a shipped order pays a fee out of their
+        // refund, a seller cancelling pays compensation from their bond, as far as it covers it.
+        let cancelled_amount = transaction.items_amount(&transaction.proposed_items);
+        let policy = get_penalty_policy(&env);
+        let mut fee = 0;
+        let mut compensation = 0;
+        if transaction.initiator == CancellationStatus::ProposedByBuyer && transaction.shipped {
+            fee = bps_of(cancelled_amount, policy.shipped_fee_bps).min(transaction.refund_amount);
+        }
+        if transaction.initiator == CancellationStatus::ProposedBySeller {
+            compensation = bps_of(cancelled_amount, policy.seller_compensation_bps).min(get_seller_bond(&env, &transaction.seller, &transaction.token));
+        }
+        
+        // Return the agreed refund less the fee to buyer and pay the rest of the items' amount to seller
+        let buyer_amount = transaction.refund_amount - fee;
+        let seller_amount = cancelled_amount - buyer_amount;
+        pay_out(&env, &transaction, buyer_amount, seller_amount);
+        
+        // Pay the compensation from the seller bond
+        if compensation > 0 {
+            token::Client::new(&env, &transaction.token).transfer(
+                &env.current_contract_address(),
+                &transaction.buyer,
+                &compensation,
+            );
+            let bond = get_seller_bond(&env, &transaction.seller, &transaction.token);
+            set_seller_bond(&env, &transaction.seller, &transaction.token, bond - compensation);
+        }
+        
+        // Cancel the items, the rest of the order stays in escrow
+        cancel_items(&env, &mut transaction);
+        
+        // Update transaction status, the transaction is cancelled once no items are left
+        transaction.status = if transaction.amount == 0 {
+            CancellationStatus::Completed
+        } else {
+            CancellationStatus::None
+        };
+        transaction.initiator = CancellationStatus::None;
+        
+        // Save updated transaction
+        save_transaction(&env, &transaction);
+        add_negotiation_entry(&env, &transaction, NegotiationAction::Agreed, &counterparty);
+        
+        // Emit event
+        Events::new(&env).cancellation_agreed(&transaction, buyer_amount, seller_amount, fee, compensation);
+        
+        Ok(())
+    }
+
+    // Counterparty answers a pending cancellation proposal with different items or a different
+    // refund amount. The counter-proposal replaces the pending terms and restarts the response window.
+    pub fn counter_propose(env: Env, id: u64, item_ids: Vec<u64>, refund_amount: i128) -> Result<(), CancellationError> {
+        // Ensure the transaction exists
+        let transaction_opt = get_transaction(&env, id);
+        if transaction_opt.is_none() {
+            return Err(CancellationError::TransactionNotFound);
+        }
+        
+        let mut transaction = transaction_opt.unwrap();
+        
+        // Check if there's a proposal in place
+        if transaction.status != CancellationStatus::ProposedByBuyer && transaction.status != CancellationStatus::ProposedBySeller {
+            return Err(CancellationError::NoProposal);
+        }
+        
+        // Check if the proposal has expired
+        let current_time = env.ledger().timestamp();
+        if current_time > proposal_deadline(&transaction) {
+            return Err(CancellationError::ProposalExpired);
+        }
+        
+        // Require counterparty auth, who becomes the proposer of the new terms
+        let counterparty = match transaction.status {
             CancellationStatus::ProposedByBuyer => {
-                transaction.seller.require_auth(); // Require seller auth
+                transaction.status = CancellationStatus::ProposedBySeller;
+                transaction.seller.clone()
             },
-            CancellationStatus::ProposedBySeller => {
-                 transaction.buyer.require_auth(); // Require buyer auth
+            _ => {
+                transaction.status = CancellationStatus::ProposedByBuyer;
+                transaction.buyer.clone()
             },
-            _ => panic!("Invalid cancellation status"),
+        };
+        counterparty.require_auth();
+        
+        // Validate the proposed items and refund
+        let proposed_items = resolve_items(&env, &transaction, &item_ids)?;
+        if refund_amount <= 0 || refund_amount > transaction.items_amount(&proposed_items) {
+            return Err(CancellationError::InvalidRefundAmount);
+        }
+        
+        // Replace the pending terms and restart the response window
+        transaction.proposal_timestamp = current_time;
+        transaction.refund_amount = refund_amount;
+        transaction.proposed_items = proposed_items;
+        
+        // Save updated transaction
+        save_transaction(&env, &transaction);
+        add_negotiation_entry(&env, &transaction, NegotiationAction::CounterProposed, &counterparty);
+        
+        // Emit event
+        Events::new(&env).counter_proposed(&transaction);
+        
+        Ok(())
+    }
+
+    // Proposer withdraws their pending cancellation proposal
+    pub fn withdraw_proposal(env: Env, id: u64) -> Result<(), CancellationError> {
+        // Ensure the transaction exists
+        let transaction_opt = get_transaction(&env, id);
+        if transaction_opt.is_none() {
+            return Err(CancellationError::TransactionNotFound);
+        }
+        
+        let mut transaction = transaction_opt.unwrap();
+        
+        // Check if there's a proposal in place
+        if transaction.status != CancellationStatus::ProposedByBuyer && transaction.status != CancellationStatus::ProposedBySeller {
+            return Err(CancellationError::NoProposal);
+        }
+        
+        // Require proposer auth
+        let proposer = get_proposer(&transaction);
+        proposer.require_auth();
+        
+        // Reset proposal
+        add_negotiation_entry(&env, &transaction, NegotiationAction::Withdrawn, &proposer);
+        transaction.status = CancellationStatus::None;
+        transaction.initiator = CancellationStatus::None;
+        transaction.refund_amount = 0;
+        transaction.proposed_items = vec![&env];
+        
+        // Save updated transaction
+        save_transaction(&env, &transaction);
+        
+        // Emit event
+        Events::new(&env).proposal_withdrawn(&transaction, proposer);
+        
+        Ok(())
+    }
+
+    // Get the arbiter of a transaction, if it has one
+    pub fn get_arbiter(env: Env, id: u64) -> Option<Address> {
+        get_arbiter(&env, id)
+    }
+
+    // Get the most recent negotiation steps of a transaction, oldest first
+    pub fn get_negotiation_history(env: Env, id: u64) -> Vec<NegotiationEntry> {
+        get_negotiation_history(&env, id)
+    }
+
+    // Seller marks the order as shipped. Can't be done while a cancellation proposal is pending.
+    pub fn mark_shipped(env: Env, id: u64) -> Result<(), CancellationError> {
+        // Ensure the transaction exists
+        let transaction_opt = get_transaction(&env, id);
+        if transaction_opt.is_none() {
+            return Err(CancellationError::TransactionNotFound);
+        }
+        
+        let mut transaction = transaction_opt.unwrap();
+        
+        // Check if cancellation is already completed
+        if transaction.status == CancellationStatus::Completed {
+            return Err(CancellationError::AlreadyCancelled);
         }
         
-        // Return funds to buyer
-        token::Client::new(&env, &transaction.token).transfer(
+        // Check if funds were already released to the seller
+        if transaction.status == CancellationStatus::Released {
+            return Err(CancellationError::AlreadyReleased);
+        }
+        
+        // Check if the transaction is waiting for the arbiter's ruling
+        if transaction.status == CancellationStatus::Escalated {
+            return Err(CancellationError::AlreadyEscalated);
+        }
+        
+        // Check that no cancellation proposal is pending
+        if transaction.status != CancellationStatus::None {
+            return Err(CancellationError::ProposalOpen);
+        }
+        
+        // Check if the order was already shipped
+        if transaction.shipped {
+            return Err(CancellationError::AlreadyShipped);
+        }
+        
+        // Require seller auth
+        transaction.seller.require_auth();
+        
+        // Update and save the transaction
+        transaction.shipped = true;
+        save_transaction(&env, &transaction);
+        
+        // Emit event
+        Events::new(&env).shipped(&transaction);
+        
+        Ok(())
+    }
+
+    // Seller deposits a bond in `token`, from which compensation for their cancellations is paid
+    pub fn deposit_bond(env: Env, seller: Address, token: Address, amount: i128) -> Result<(), CancellationError> {
+        // Validate inputs
+        if amount <= 0 {
+            return Err(CancellationError::InvalidAmount);
+        }
+        
+        // Require seller auth
+        seller.require_auth();
+        
+        // Transfer the bond from seller to the contract
+        token::Client::new(&env, &token).transfer(
+            &seller,
             &env.current_contract_address(),
-            &transaction.buyer,
-            &transaction.amount,
+            &amount,
         );
         
-        // Update transaction status
-        transaction.status = CancellationStatus::Completed;
+        let bond = get_seller_bond(&env, &seller, &token);
+        set_seller_bond(&env, &seller, &token, bond + amount);
+        
+        Ok(())
+    }
+
+    // Seller withdraws part of their bond in `token`. The compensation owed on pending proposals
+    // the seller started stays in the bond until they are agreed to, withdrawn or expire.
+    pub fn withdraw_bond(env: Env, seller: Address, token: Address, amount: i128) -> Result<(), CancellationError> {
+        // Validate inputs
+        if amount <= 0 {
+            return Err(CancellationError::InvalidAmount);
+        }
+        
+        // Require seller auth
+        seller.require_auth();
+        
+        // Check the bond covers the withdrawal and the compensation owed on the seller's pending proposals
+        let bond = get_seller_bond(&env, &seller, &token);
+        if bond - get_reserved_bond(&env, &seller, &token) < amount {
+            return Err(CancellationError::InsufficientBond);
+        }
+        
+        // Transfer the bond back to the seller
+        token::Client::new(&env, &token).transfer(
+            &env.current_contract_address(),
+            &seller,
+            &amount,
+        );
+        set_seller_bond(&env, &seller, &token, bond - amount);
+        
+        Ok(())
+    }
+
+    // Buyer confirms receipt and releases the escrowed funds to the seller
+    pub fn confirm_receipt(env: Env, id: u64) -> Result<(), CancellationError> {
+        // Ensure the transaction exists
+        let transaction_opt = get_transaction(&env, id);
+        if transaction_opt.is_none() {
+            return Err(CancellationError::TransactionNotFound);
+        }
+        
+        let mut transaction = transaction_opt.unwrap();
+        
+        // Check if cancellation is already completed
+        if transaction.status == CancellationStatus::Completed {
+            return Err(CancellationError::AlreadyCancelled);
+        }
+        
+        // Check if funds were already released to the seller
+        if transaction.status == CancellationStatus::Released {
+            return Err(CancellationError::AlreadyReleased);
+        }
+        
+        // Check if the transaction is waiting for the arbiter's ruling
+        if transaction.status == CancellationStatus::Escalated {
+            return Err(CancellationError::AlreadyEscalated);
+        }
+        
+        // Require buyer auth
+        transaction.buyer.require_auth();
+        
+        // Release funds to seller, superseding any pending cancellation proposal
+        release_funds(&env, &mut transaction, false);
+        
+        Ok(())
+    }
+
+    // Release the escrowed funds to the seller once the transaction's auto-release period
+    // has passed without an open cancellation proposal. Anyone can call this.
+    pub fn auto_release(env: Env, id: u64) -> Result<(), CancellationError> {
+        // Ensure the transaction exists
+        let transaction_opt = get_transaction(&env, id);
+        if transaction_opt.is_none() {
+            return Err(CancellationError::TransactionNotFound);
+        }
+        
+        let mut transaction = transaction_opt.unwrap();
+        
+        // Check if cancellation is already completed
+        if transaction.status == CancellationStatus::Completed {
+            return Err(CancellationError::AlreadyCancelled);
+        }
+        
+        // Check if funds were already released to the seller
+        if transaction.status == CancellationStatus::Released {
+            return Err(CancellationError::AlreadyReleased);
+        }
+        
+        // Check if the transaction is waiting for the arbiter's ruling
+        if transaction.status == CancellationStatus::Escalated {
+            return Err(CancellationError::AlreadyEscalated);
+        }
+        
+        // Check if auto-release is enabled for this transaction
+        if transaction.auto_release_period == 0 {
+            return Err(CancellationError::AutoReleaseDisabled);
+        }
+        
+        // Check if the auto-release period has passed
+        let current_time = env.ledger().timestamp();
+        if current_time < transaction.created_timestamp + transaction.auto_release_period {
+            return Err(CancellationError::AutoReleaseNotDue);
+        }
+        
+        // Check that no cancellation proposal is still open
+        if transaction.status != CancellationStatus::None && current_time <= proposal_deadline(&transaction) {
+            return Err(CancellationError::ProposalOpen);
+        }
+        
+        // Release funds to seller
+        release_funds(&env, &mut transaction, true);
+        
+        Ok(())
+    }
+
+    // Buyer or seller escalates a deadlocked transaction to its arbiter, once enough proposals
+    // have expired or the transaction is old enough under the escalation policy. Any pending
+    // proposal is dropped.
+    pub fn escalate_to_arbiter(env: Env, id: u64, party: Address) -> Result<(), CancellationError> {
+        // Ensure the transaction exists
+        let transaction_opt = get_transaction(&env, id);
+        if transaction_opt.is_none() {
+            return Err(CancellationError::TransactionNotFound);
+        }
+        
+        let mut transaction = transaction_opt.unwrap();
+        
+        // Check if cancellation is already completed
+        if transaction.status == CancellationStatus::Completed {
+            return Err(CancellationError::AlreadyCancelled);
+        }
+        
+        // Check if funds were already released to the seller
+        if transaction.status == CancellationStatus::Released {
+            return Err(CancellationError::AlreadyReleased);
+        }
+        
+        // Check if the transaction was already escalated
+        if transaction.status == CancellationStatus::Escalated {
+            return Err(CancellationError::AlreadyEscalated);
+        }
+        
+        // Require auth from the buyer or the seller
+        if party != transaction.buyer && party != transaction.seller {
+            return Err(CancellationError::NotAParty);
+        }
+        party.require_auth();
+        
+        // Check that there is an arbiter to escalate to
+        let arbiter = match get_arbiter(&env, id) {
+            Some(arbiter) => arbiter,
+            None => return Err(CancellationError::NoArbiter),
+        };
+        
+        // Count a pending proposal whose response window has passed as expired
+        if is_proposal_expired(&env, &transaction) {
+            add_negotiation_entry(&env, &transaction, NegotiationAction::Expired, &get_proposer(&transaction));
+            transaction.expired_proposals += 1;
+        }
+        
+        // Check if an escalation threshold has been reached
+        let policy = get_escalation_policy(&env);
+        let age = env.ledger().timestamp() - transaction.created_timestamp;
+        let due = (policy.max_expired_proposals > 0 && transaction.expired_proposals >= policy.max_expired_proposals)
+            || (policy.max_age > 0 && age >= policy.max_age);
+        if !due {
+            return Err(CancellationError::EscalationNotDue);
+        }
+        
+        // Drop any pending proposal and wait for the arbiter
+        transaction.status = CancellationStatus::Escalated;
+        transaction.initiator = CancellationStatus::None;
+        transaction.refund_amount = 0;
+        transaction.proposed_items = vec![&env];
+        
+        // Save updated transaction
+        save_transaction(&env, &transaction);
+        add_negotiation_entry(&env, &transaction, NegotiationAction::Escalated, &party);
+        
+        // Emit event
+        Events::new(&env).escalated(&transaction, party, arbiter);
+        
+        Ok(())
+    }
+
+    // Arbiter rules on an escalated transaction, refunding the buyer, releasing the funds to
+    // the seller, or splitting them between both
+    pub fn rule_on_escalation(env: Env, id: u64, ruling: Ruling) -> Result<(), CancellationError> {
+        // Ensure the transaction exists
+        let transaction_opt = get_transaction(&env, id);
+        if transaction_opt.is_none() {
+            return Err(CancellationError::TransactionNotFound);
+        }
+        
+        let mut transaction = transaction_opt.unwrap();
+        
+        // Check if the transaction is waiting for a ruling
+        if transaction.status != CancellationStatus::Escalated {
+            return Err(CancellationError::NotEscalated);
+        }
+        
+        // Require arbiter auth
+        let arbiter = match get_arbiter(&env, id) {
+            Some(arbiter) => arbiter,
+            None => return Err(CancellationError::NoArbiter),
+        };
+        arbiter.require_auth();
+        
+        // Work out the buyer's refund
+        transaction.refund_amount = match ruling {
+            Ruling::Refund => transaction.amount,
+            Ruling::Release => 0,
+            Ruling::Split(refund_amount) => {
+                if refund_amount <= 0 || refund_amount >= transaction.amount {
+                    return Err(CancellationError::InvalidRefundAmount);
+                }
+                refund_amount
+            }
+        };
+        
+        // Pay out the funds, a ruling without refund releases them to the seller, any other
+        // ruling cancels the remaining items as an agreed cancellation does
+        let buyer_amount = transaction.refund_amount;
+        let seller_amount = transaction.amount - buyer_amount;
+        transaction.proposed_items = resolve_items(&env, &transaction, &vec![&env])?;
+        pay_out(&env, &transaction, buyer_amount, seller_amount);
+        transaction.status = if buyer_amount == 0 {
+            CancellationStatus::Released
+        } else {
+            cancel_items(&env, &mut transaction);
+            CancellationStatus::Completed
+        };
         
         // Save updated transaction
         save_transaction(&env, &transaction);
+        add_negotiation_entry(&env, &transaction, NegotiationAction::Ruled, &arbiter);
         
         // Emit event
-        Events::new(&env).cancellation_agreed(&transaction);
+        Events::new(&env).arbiter_ruled(&transaction, arbiter, buyer_amount, seller_amount);
+        
+        Ok(())
     }
 
     // Check if a cancellation proposal has expired
-    pub fn check_cancellation_expiry(env: Env, id: u64) -> bool {
+    pub fn check_cancellation_expiry(env: Env, id: u64) -> Result<bool, CancellationError> {
         // Ensure the transaction exists
         let transaction_opt = get_transaction(&env, id);
         if transaction_opt.is_none() {
-            panic!("Transaction not found");
+            return Err(CancellationError::TransactionNotFound);
         }
         
         let transaction = transaction_opt.unwrap();
         
-        // Check if there's a proposal in place
-        if transaction.status == CancellationStatus::None || transaction.status == CancellationStatus::Completed {
-            return false;
+        Ok(is_proposal_expired(&env, &transaction))
+    }
+
+    // Get the status of a transaction as of the current ledger time. A pending proposal whose
+    // response window has passed is reported as `Expired`, even before it is reset.
+    pub fn get_effective_status(env: Env, id: u64) -> Result<CancellationStatus, CancellationError> {
+        // Ensure the transaction exists
+        let transaction_opt = get_transaction(&env, id);
+        if transaction_opt.is_none() {
+            return Err(CancellationError::TransactionNotFound);
         }
         
-        // Check if the proposal has expired
-        let current_time = env.ledger().timestamp();
-        if current_time > transaction.proposal_timestamp + transaction.response_window {
-            return true;
-        }
+        let transaction = transaction_opt.unwrap();
         
-        false
+        Ok(effective_status(&env, &transaction))
     }
 
     // Reset an expired cancellation proposal
-    pub fn reset_expired_proposal(env: Env, id: u64) {
+    pub fn reset_expired_proposal(env: Env, id: u64) -> Result<(), CancellationError> {
         // Ensure the transaction exists
         let transaction_opt = get_transaction(&env, id);
         if transaction_opt.is_none() {
-            panic!("Transaction not found");
+            return Err(CancellationError::TransactionNotFound);
         }
         
         let mut transaction = transaction_opt.unwrap();
         
         // Check if there's a proposal in place
-        if transaction.status == CancellationStatus::None || transaction.status == CancellationStatus::Completed {
-            panic!("No cancellation proposal to reset");
+        if transaction.status != CancellationStatus::ProposedByBuyer && transaction.status != CancellationStatus::ProposedBySeller {
+            return Err(CancellationError::NoProposal);
         }
         
         // Check if the proposal has expired
         let current_time = env.ledger().timestamp();
-        if current_time <= transaction.proposal_timestamp + transaction.response_window {
-            panic!("Cancellation proposal has not expired");
+        if current_time <= proposal_deadline(&transaction) {
+            return Err(CancellationError::ProposalNotExpired);
         }
         
         // Reset proposal
+        add_negotiation_entry(&env, &transaction, NegotiationAction::Expired, &get_proposer(&transaction));
         transaction.status = CancellationStatus::None;
+        transaction.initiator = CancellationStatus::None;
+        transaction.refund_amount = 0;
+        transaction.proposed_items = vec![&env];
+        transaction.expired_proposals += 1;
         
         // Save updated transaction
         save_transaction(&env, &transaction);
         
         // Emit event
         Events::new(&env).cancellation_expired(&transaction);
+        
+        Ok(())
     }
 
     // Get the current response window setting
     pub fn get_response_window(env: Env) -> u64 {
         get_response_window(&env)
     }
+
+    // Get the allowed range for response windows
+    pub fn get_response_window_bounds(env: Env) -> WindowBounds {
+        get_response_window_bounds(&env)
+    }
+
+    // Get the contract admin
+    pub fn get_admin(env: Env) -> Result<Address, CancellationError> {
+        get_admin(&env)
+    }
+
+    // Get the current auto-release period setting
+    pub fn get_auto_release_period(env: Env) -> u64 {
+        get_auto_release_period(&env)
+    }
+
+    // Get the arbiter for transactions created without one
+    pub fn get_default_arbiter(env: Env) -> Option<Address> {
+        get_default_arbiter(&env)
+    }
+
+    // Get the current penalty policy
+    pub fn get_penalty_policy(env: Env) -> PenaltyPolicy {
+        get_penalty_policy(&env)
+    }
+
+    // Get a seller's bond in `token`
+    pub fn get_seller_bond(env: Env, seller: Address, token: Address) -> i128 {
+        get_seller_bond(&env, &seller, &token)
+    }
+
+    // Get the current escalation thresholds
+    pub fn get_escalation_policy(env: Env) -> EscalationPolicy {
+        get_escalation_policy(&env)
+    }
 } 
\ No newline at end of file
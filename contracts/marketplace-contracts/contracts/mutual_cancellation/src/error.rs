@@ -0,0 +1,35 @@
+use soroban_sdk::contracterror;
+
+// Errors returned by the contract entrypoints. A failed call is rolled back, so no
+// state change or event of that call is kept.
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum CancellationError {
+    NotInitialized = 1,       // No admin has been recorded yet
+    AlreadyInitialized = 2,   // The contract was already initialized
+    WindowOutOfBounds = 3,    // Response window outside the configured bounds
+    AlreadyMigrated = 4,      // No transaction is left to move to persistent storage
+    InvalidAmount = 5,        // Transaction amount is not positive
+    TransactionNotFound = 6,  // No transaction with this ID
+    AlreadyCancelled = 7,     // Cancellation already completed
+    AlreadyReleased = 8,      // Funds already released to the seller
+    AlreadyProposed = 9,      // A cancellation proposal is still pending
+    NoProposal = 10,          // No pending cancellation proposal
+    ProposalExpired = 11,     // The proposal's response window has passed
+    ProposalNotExpired = 12,  // The proposal's response window has not passed yet
+    InvalidRefundAmount = 13, // Refund is not positive or exceeds the escrowed amount
+    AutoReleaseDisabled = 14, // Transaction has no auto-release period
+    AutoReleaseNotDue = 15,   // Auto-release period has not passed yet
+    ProposalOpen = 16,        // A pending cancellation proposal blocks the call
+    NoArbiter = 17,           // Transaction has no arbiter to escalate to
+    EscalationNotDue = 18,    // Neither escalation threshold has been reached
+    AlreadyEscalated = 19,    // Transaction is waiting for the arbiter's ruling
+    NotEscalated = 20,        // Transaction was not escalated to the arbiter
+    NotAParty = 21,           // Address is neither the buyer nor the seller
+    InvalidItems = 22,        // Line items are missing, duplicated, unknown or already cancelled
+    InvalidPolicy = 23,       // Penalty rate above 100%
+    AlreadyShipped = 24,      // The order was already marked as shipped
+    InsufficientBond = 25,    // Seller bond is smaller than the withdrawal
+    InvalidArbiter = 26,      // Arbiter is the buyer or the seller of the transaction
+}
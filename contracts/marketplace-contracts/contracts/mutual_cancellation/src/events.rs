@@ -1,5 +1,5 @@
 use crate::storage_types::{Transaction, CancellationStatus};
-use soroban_sdk::{symbol_short, Env};
+use soroban_sdk::{symbol_short, Address, Env};
 
 pub struct Events {
     env: Env,
@@ -40,12 +40,45 @@ impl Events {
         );
         self.env.events().publish(
             topics, 
-            (transaction.proposal_timestamp, transaction.response_window)
+            (transaction.proposal_timestamp, transaction.response_window, transaction.refund_amount, transaction.proposed_items.clone())
         );
     }
 
-    // Emit an event when a cancellation is agreed to
-    pub fn cancellation_agreed(&self, transaction: &Transaction) {
+    // Emit an event when the counterparty answers a proposal with different terms
+    pub fn counter_proposed(&self, transaction: &Transaction) {
+        let proposer = match transaction.status {
+            CancellationStatus::ProposedByBuyer => transaction.buyer.clone(),
+            CancellationStatus::ProposedBySeller => transaction.seller.clone(),
+            _ => panic!("Invalid cancellation status for counter-proposal event"),
+        };
+
+        let topics = (
+            symbol_short!("can_count"),
+            transaction.id,
+            proposer,
+        );
+        self.env.events().publish(
+            topics, 
+            (transaction.proposal_timestamp, transaction.response_window, transaction.refund_amount, transaction.proposed_items.clone())
+        );
+    }
+
+    // Emit an event when the proposer withdraws a cancellation proposal
+    pub fn proposal_withdrawn(&self, transaction: &Transaction, proposer: Address) {
+        let topics = (
+            symbol_short!("can_wdraw"),
+            transaction.id,
+            proposer,
+        );
+        self.env.events().publish(
+            topics, 
+            transaction.status
+        );
+    }
+
+    // Emit an event when a cancellation is agreed to, with the amounts paid out of escrow to
+    // each party, the fee the buyer paid the seller and the compensation paid from the seller bond
+    pub fn cancellation_agreed(&self, transaction: &Transaction, buyer_amount: i128, seller_amount: i128, fee: i128, compensation: i128) {
         let topics = (
             symbol_short!("can_agree"),
             transaction.id,
@@ -54,7 +87,85 @@ impl Events {
         );
         self.env.events().publish(
             topics, 
-            transaction.amount
+            (buyer_amount, seller_amount, fee, compensation, transaction.proposed_items.clone())
+        );
+    }
+
+    // Emit an event when funds are released to the seller
+    pub fn funds_released(&self, transaction: &Transaction, auto_released: bool) {
+        let topics = (
+            symbol_short!("released"),
+            transaction.id,
+            transaction.buyer.clone(),
+            transaction.seller.clone(),
+        );
+        self.env.events().publish(
+            topics, 
+            (transaction.amount, auto_released)
+        );
+    }
+
+    // Emit an event when the seller marks an order as shipped
+    pub fn shipped(&self, transaction: &Transaction) {
+        let topics = (
+            symbol_short!("shipped"),
+            transaction.id,
+            transaction.seller.clone(),
+        );
+        self.env.events().publish(
+            topics, 
+            transaction.buyer.clone()
+        );
+    }
+
+    // Emit an event when a seller deposits or withdraws bond
+    pub fn bond_updated(&self, seller: Address, token: Address, bond: i128) {
+        let topics = (
+            symbol_short!("bond"),
+            seller,
+            token,
+        );
+        self.env.events().publish(
+            topics, 
+            bond
+        );
+    }
+
+    // Emit an event when the admin changes the default response window
+    pub fn response_window_updated(&self, admin: Address, response_window: u64) {
+        let topics = (
+            symbol_short!("resp_win"),
+            admin,
+        );
+        self.env.events().publish(
+            topics, 
+            response_window
+        );
+    }
+
+    // Emit an event when a party escalates a transaction to the arbiter
+    pub fn escalated(&self, transaction: &Transaction, party: Address, arbiter: Address) {
+        let topics = (
+            symbol_short!("escalated"),
+            transaction.id,
+            party,
+        );
+        self.env.events().publish(
+            topics, 
+            (arbiter, transaction.expired_proposals)
+        );
+    }
+
+    // Emit an event when the arbiter rules on an escalated transaction
+    pub fn arbiter_ruled(&self, transaction: &Transaction, arbiter: Address, buyer_amount: i128, seller_amount: i128) {
+        let topics = (
+            symbol_short!("arb_ruled"),
+            transaction.id,
+            arbiter,
+        );
+        self.env.events().publish(
+            topics, 
+            (buyer_amount, seller_amount)
         );
     }
 
@@ -1,8 +1,10 @@
 #![no_std]
 
 mod contract;
+mod error;
 mod storage_types;
 mod events;
 mod test;
 
-pub use crate::contract::MutualCancellationClient; 
\ No newline at end of file
+pub use crate::contract::MutualCancellationClient;
+pub use crate::error::CancellationError; 
\ No newline at end of file
@@ -1,4 +1,4 @@
-use soroban_sdk::{contracttype, Address};
+use soroban_sdk::{contracttype, vec, Address, Env, Vec};
 
 pub(crate) const DAY_IN_LEDGERS: u32 = 17280;
 pub(crate) const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
@@ -7,14 +7,120 @@ pub(crate) const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_I
 pub(crate) const TRANSACTION_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
 pub(crate) const TRANSACTION_LIFETIME_THRESHOLD: u32 = TRANSACTION_BUMP_AMOUNT - DAY_IN_LEDGERS;
 
+// Maximum number of negotiation steps kept per transaction, oldest are dropped first
+pub(crate) const MAX_NEGOTIATION_HISTORY: u32 = 20;
+
+// Maximum number of line items in a transaction
+pub(crate) const MAX_LINE_ITEMS: u32 = 50;
+
+// Basis points making up a whole amount
+pub(crate) const BPS_DENOMINATOR: u32 = 10_000;
+
+// Maximum number of transactions read, and so returned, by one page of a buyer or seller query
+pub(crate) const MAX_PAGE_SIZE: u32 = 50;
+
+// Maximum number of transaction IDs one `migrate_storage` call walks
+pub(crate) const MAX_MIGRATION_BATCH: u32 = 25;
+
 // Status of a cancellation proposal
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[contracttype]
 pub enum CancellationStatus {
     None,                 // No cancellation proposal exists
     ProposedByBuyer,      // Buyer has proposed cancellation
     ProposedBySeller,     // Seller has proposed cancellation
     Completed,            // Cancellation completed and funds returned
+    Released,             // Delivery confirmed and funds released to the seller
+    Escalated,            // Escalated to the arbiter, who rules on how the funds are paid out
+    Expired,              // Proposal's response window has passed, only reported by `get_effective_status`
+}
+
+// Allowed range for response windows, in seconds
+#[derive(Clone)]
+#[contracttype]
+pub struct WindowBounds {
+    pub min: u64,                  // Shortest allowed response window
+    pub max: u64,                  // Longest allowed response window
+}
+
+// When a party can escalate a transaction to its arbiter, 0 disables a threshold
+#[derive(Clone)]
+#[contracttype]
+pub struct EscalationPolicy {
+    pub max_expired_proposals: u32, // Number of proposals that expired without a response
+    pub max_age: u64,               // Time since the transaction was created
+}
+
+// One page of a buyer or seller query
+#[derive(Clone)]
+#[contracttype]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>, // Matching transactions, oldest first
+    pub next_offset: u32,               // Offset to continue from, the number of transactions once all were read
+}
+
+// A pending cancellation proposal in a party's proposals index
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct PendingProposal {
+    pub id: u64,                    // Transaction ID
+    pub status: CancellationStatus, // `ProposedByBuyer` or `ProposedBySeller`
+    pub deadline: u64,              // End of the response window
+}
+
+// Penalties applied when a cancellation is agreed to, in basis points of the cancelled items' amount
+#[derive(Clone)]
+#[contracttype]
+pub struct PenaltyPolicy {
+    pub shipped_fee_bps: u32,          // Fee the buyer pays the seller for cancelling a shipped order
+    pub seller_compensation_bps: u32,  // Compensation the seller pays the buyer from their bond for cancelling
+}
+
+// Arbiter's decision on an escalated transaction
+#[derive(Clone)]
+#[contracttype]
+pub enum Ruling {
+    Refund,               // Refund the whole amount to the buyer
+    Release,              // Release the whole amount to the seller
+    Split(i128),          // Refund this amount to the buyer and pay the rest to the seller
+}
+
+impl WindowBounds {
+    pub fn contains(&self, response_window: u64) -> bool {
+        self.min <= response_window && response_window <= self.max
+    }
+}
+
+// Step in the cancellation negotiation of a transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[contracttype]
+pub enum NegotiationAction {
+    Proposed,             // A party proposed cancellation
+    CounterProposed,      // The counterparty replaced the pending terms with their own
+    Withdrawn,            // The proposer withdrew their proposal
+    Expired,              // The proposal expired without a response
+    Agreed,               // The counterparty agreed and the cancellation completed
+    Escalated,            // A party escalated the transaction to the arbiter
+    Ruled,                // The arbiter ruled on the escalated transaction
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct NegotiationEntry {
+    pub action: NegotiationAction, // What happened
+    pub party: Address,            // Party who acted, the proposer for expired proposals
+    pub refund_amount: i128,       // Refund to the buyer in the terms at that point
+    pub items: Vec<u64>,           // IDs of the line items the terms cancel
+    pub timestamp: u64,            // Timestamp of the step
+}
+
+// Line item of an order, cancelled items are refunded and no longer released to the seller
+#[derive(Clone)]
+#[contracttype]
+pub struct LineItem {
+    pub id: u64,                   // Item ID, unique within the transaction
+    pub amount: i128,              // Amount of tokens paid for the item
+    pub cancelled: bool,           // Whether the item was cancelled by an agreed proposal
 }
 
 #[derive(Clone)]
@@ -24,18 +130,95 @@ pub struct Transaction {
     pub buyer: Address,            // Buyer's address
     pub seller: Address,           // Seller's address
     pub token: Address,            // Token contract address 
-    pub amount: i128,              // Amount of tokens in escrow
+    pub amount: i128,              // Amount of tokens in escrow, the total of the items not cancelled
+    pub items: Vec<LineItem>,      // Line items of the order
     pub status: CancellationStatus, // Current status of cancellation
     pub proposal_timestamp: u64,   // Timestamp when cancellation was proposed
     pub response_window: u64,      // Time window for the counterparty to respond
+    pub created_timestamp: u64,    // Timestamp when the transaction was created
+    pub auto_release_period: u64,  // Time after creation when funds can be released to the seller, 0 if disabled
+    pub refund_amount: i128,       // Refund to the buyer in the pending or agreed cancellation, the seller keeps the rest
+    pub proposed_items: Vec<u64>,  // IDs of the line items in the pending or agreed cancellation
+    pub expired_proposals: u32,    // Number of cancellation proposals that expired without a response
+    pub shipped: bool,             // Whether the seller marked the order as shipped
+    pub initiator: CancellationStatus, // Who started the pending negotiation, kept through counter-proposals, `None` without one
+}
+
+// Transaction layout of the first version of the contract, which kept it in instance storage
+#[derive(Clone)]
+#[contracttype]
+pub struct LegacyTransaction {
+    pub id: u64,
+    pub buyer: Address,
+    pub seller: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub status: CancellationStatus,
+    pub proposal_timestamp: u64,
+    pub response_window: u64,
+}
+
+impl LegacyTransaction {
+    // Convert to the current layout. The order becomes a single line item with ID 1, and a
+    // pending or agreed cancellation covers all of it with a full refund, as in the first version.
+    // Auto-release stays disabled, and the transaction counts as created at `timestamp`.
+    pub fn into_transaction(self, env: &Env, timestamp: u64) -> Transaction {
+        let cancelled = self.status == CancellationStatus::Completed;
+        let proposed = self.status != CancellationStatus::None;
+        Transaction {
+            id: self.id,
+            buyer: self.buyer,
+            seller: self.seller,
+            token: self.token,
+            amount: if cancelled { 0 } else { self.amount },
+            items: vec![env, LineItem { id: 1, amount: self.amount, cancelled }],
+            status: self.status,
+            proposal_timestamp: self.proposal_timestamp,
+            response_window: self.response_window,
+            created_timestamp: timestamp,
+            auto_release_period: 0,
+            refund_amount: if proposed { self.amount } else { 0 },
+            proposed_items: if proposed { vec![env, 1] } else { vec![env] },
+            expired_proposals: 0,
+            shipped: false,
+            initiator: if proposed && !cancelled { self.status } else { CancellationStatus::None },
+        }
+    }
+}
+
+impl Transaction {
+    // Total amount of the given line items
+    pub fn items_amount(&self, item_ids: &Vec<u64>) -> i128 {
+        self.items
+            .iter()
+            .filter(|item| item_ids.contains(item.id))
+            .map(|item| item.amount)
+            .sum()
+    }
 }
 
+// Transactions, negotiation histories and per-party indexes live in persistent storage,
+// contract-wide settings in instance storage
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
+    Admin,                        // Address allowed to change contract-wide settings
     TransactionCounter,           // Counter for generating unique transaction IDs
     Transaction(u64),             // Transaction data by ID
     BuyerTransactions(Address),   // List of transaction IDs for a buyer
     SellerTransactions(Address),  // List of transaction IDs for a seller
+    BuyerStatusCount(Address, CancellationStatus),  // Number of a buyer's transactions in a status
+    SellerStatusCount(Address, CancellationStatus), // Number of a seller's transactions in a status
+    BuyerProposals(Address),      // Pending cancellation proposals on a buyer's transactions
+    SellerProposals(Address),     // Pending cancellation proposals on a seller's transactions
+    NegotiationHistory(u64),      // Recent negotiation steps by transaction ID
+    Arbiter(u64),                 // Arbiter by transaction ID, for transactions that have one
     ResponseWindow,               // Default time window for responding to cancellation requests
+    ResponseWindowBounds,         // Allowed range for the default and per-transaction response windows
+    MigrationCursor,              // Transaction ID the storage migration continues from
+    AutoReleasePeriod,            // Default time after which funds can be released without a confirmation
+    DefaultArbiter,               // Arbiter for transactions created without one
+    EscalationPolicy,             // When transactions can be escalated to their arbiter
+    PenaltyPolicy,                // Penalties applied to agreed cancellations
+    SellerBond(Address, Address), // Bond a seller holds in a token, by seller and token address
 } 
\ No newline at end of file
@@ -2,52 +2,86 @@
 
 use crate::{
     contract::{MutualCancellation, MutualCancellationClient},
-    storage_types::{CancellationStatus, Transaction},
+    error::CancellationError,
+    storage_types::{CancellationStatus, DataKey, EscalationPolicy, LegacyTransaction, NegotiationAction, PenaltyPolicy, Ruling, WindowBounds, MAX_PAGE_SIZE},
 };
 use soroban_sdk::{
-    testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger, LedgerInfo},
-    token, Address, BytesN, Env, IntoVal, Symbol,
+    testutils::{Address as _, Events, Ledger, LedgerInfo},
+    token, vec, Address, Env, IntoVal, Symbol, Val, Vec,
 };
 
 // Helper to create a token contract for testing
-fn create_token_contract(e: &Env, admin: &Address) -> (Address, token::Client) {
+fn create_token_contract<'a>(e: &Env, admin: &Address) -> (Address, token::Client<'a>) {
     let contract_address = e.register_stellar_asset_contract(admin.clone());
     (contract_address.clone(), token::Client::new(e, &contract_address))
 }
 
 // Helper to create test users with initial token balance
-fn create_user(e: &Env, token: &token::Client, admin: &Address, amount: i128) -> Address {
+fn create_user(e: &Env, token: &token::Client, amount: i128) -> Address {
     let user = Address::generate(e);
-    token.mint(&admin, &user, &amount);
+    token::StellarAssetClient::new(e, &token.address).mint(&user, &amount);
     user
 }
 
+// Helper to collect the events emitted by the mutual cancellation contract, leaving out token events
+fn contract_events(env: &Env, client: &MutualCancellationClient) -> Vec<(Address, Vec<Val>, Val)> {
+    let mut events = Vec::new(env);
+    for event in env.events().all().iter() {
+        if event.0 == client.address {
+            events.push_back(event);
+        }
+    }
+    events
+}
+
+// Helper to check an event topic against an expected value
+fn assert_topic(env: &Env, topics: &Vec<Val>, index: u32, expected: impl IntoVal<Env, Val>) {
+    assert_eq!(vec![env, topics.get(index).unwrap()], vec![env, expected.into_val(env)]);
+}
+
+// Helper to check that `signer` is the only address that had to authorize the last call
+fn assert_authorized_by(env: &Env, signer: &Address) {
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, *signer);
+}
+
 // Helper to set up a standard test environment
-fn setup_test() -> (
+fn setup_test<'a>() -> (
     Env,
-    MutualCancellationClient,
+    MutualCancellationClient<'a>,
     Address,
     Address,
     Address,
-    token::Client,
+    token::Client<'a>,
 ) {
     let env = Env::default();
     env.mock_all_auths();
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp = 1_700_000_000;
+    });
 
     // Set up the token
     let admin = Address::generate(&env);
     let (token_address, token_client) = create_token_contract(&env, &admin);
 
     // Create buyer and seller with funds
-    let buyer = create_user(&env, &token_client, &admin, 10000);
+    let buyer = create_user(&env, &token_client, 10000);
     let seller = Address::generate(&env);
 
     // Set up the mutual cancellation contract
     let contract_id = env.register_contract(None, MutualCancellation);
     let client = MutualCancellationClient::new(&env, &contract_id);
 
-    // Initialize the contract with a 7-day response window (simulating 7 days in seconds)
-    client.initialize(&(7 * 24 * 60 * 60));
+    // Initialize the contract with a 7-day response window (allowed between 1 and 30 days)
+    // and a 30-day auto-release period
+    let contract_admin = Address::generate(&env);
+    client.initialize(
+        &contract_admin,
+        &(7 * 24 * 60 * 60),
+        &WindowBounds { min: 24 * 60 * 60, max: 30 * 24 * 60 * 60 },
+        &(30 * 24 * 60 * 60),
+    );
 
     (env, client, buyer, seller, token_address, token_client)
 }
@@ -60,7 +94,7 @@ fn create_test_transaction(
     token_address: &Address,
     amount: i128,
 ) -> u64 {
-    client.create_transaction(buyer, seller, token_address, &amount)
+    client.create_transaction(buyer, seller, token_address, &vec![&client.env, (1, amount)], &None, &None)
 }
 
 // Test successful creation of a transaction
@@ -69,7 +103,7 @@ fn test_create_transaction() {
     let (env, client, buyer, seller, token_address, token_client) = setup_test();
 
     // Initial balance of buyer and contract
-    let contract_address = env.current_contract_address();
+    let contract_address = client.address.clone();
     let initial_buyer_balance = token_client.balance(&buyer);
     let initial_contract_balance = token_client.balance(&contract_address);
     
@@ -93,22 +127,22 @@ fn test_create_transaction() {
     assert_eq!(final_contract_balance, initial_contract_balance + amount);
     
     // Verify transaction shows up in buyer's and seller's transactions
-    let buyer_txs = client.get_buyer_transactions(&buyer);
-    let seller_txs = client.get_seller_transactions(&seller);
+    let buyer_txs = client.get_buyer_transactions(&buyer, &None, &0, &10).transactions;
+    let seller_txs = client.get_seller_transactions(&seller, &None, &0, &10).transactions;
     assert_eq!(buyer_txs.len(), 1);
     assert_eq!(seller_txs.len(), 1);
     assert_eq!(buyer_txs.get(0).unwrap().id, tx_id);
     assert_eq!(seller_txs.get(0).unwrap().id, tx_id);
     
     // Verify event was emitted
-    let events = env.events().all();
+    let events = contract_events(&env, &client);
     assert_eq!(events.len(), 1);
     
-    let topics = events[0].topics.clone();
-    assert_eq!(topics.get(0).unwrap(), Symbol::new(&env, "tx_created").into_val(&env));
-    assert_eq!(topics.get(1).unwrap(), tx_id.into_val(&env));
-    assert_eq!(topics.get(2).unwrap(), buyer.into_val(&env));
-    assert_eq!(topics.get(3).unwrap(), seller.into_val(&env));
+    let (_, topics, _) = events.get(0).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "tx_create"));
+    assert_topic(&env, &topics, 1, tx_id);
+    assert_topic(&env, &topics, 2, buyer.clone());
+    assert_topic(&env, &topics, 3, seller.clone());
 }
 
 // Test proposal of cancellation by the buyer
@@ -121,7 +155,7 @@ fn test_buyer_proposes_cancellation() {
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Buyer proposes cancellation
-    client.buyer_propose_cancellation(&tx_id);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
     // Verify transaction status is updated
     let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
@@ -129,13 +163,13 @@ fn test_buyer_proposes_cancellation() {
     assert!(tx.proposal_timestamp > 0);
     
     // Verify event was emitted
-    let events = env.events().all();
+    let events = contract_events(&env, &client);
     assert_eq!(events.len(), 2); // Transaction creation + proposal
     
-    let topics = events[1].topics.clone();
-    assert_eq!(topics.get(0).unwrap(), Symbol::new(&env, "can_prop").into_val(&env));
-    assert_eq!(topics.get(1).unwrap(), tx_id.into_val(&env));
-    assert_eq!(topics.get(2).unwrap(), buyer.into_val(&env));
+    let (_, topics, _) = events.get(1).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "can_prop"));
+    assert_topic(&env, &topics, 1, tx_id);
+    assert_topic(&env, &topics, 2, buyer.clone());
 }
 
 // Test proposal of cancellation by the seller
@@ -148,7 +182,7 @@ fn test_seller_proposes_cancellation() {
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Seller proposes cancellation
-    client.seller_propose_cancellation(&tx_id);
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
     // Verify transaction status is updated
     let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
@@ -156,13 +190,13 @@ fn test_seller_proposes_cancellation() {
     assert!(tx.proposal_timestamp > 0);
     
     // Verify event was emitted
-    let events = env.events().all();
+    let events = contract_events(&env, &client);
     assert_eq!(events.len(), 2); // Transaction creation + proposal
     
-    let topics = events[1].topics.clone();
-    assert_eq!(topics.get(0).unwrap(), Symbol::new(&env, "can_prop").into_val(&env));
-    assert_eq!(topics.get(1).unwrap(), tx_id.into_val(&env));
-    assert_eq!(topics.get(2).unwrap(), seller.into_val(&env));
+    let (_, topics, _) = events.get(1).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "can_prop"));
+    assert_topic(&env, &topics, 1, tx_id);
+    assert_topic(&env, &topics, 2, seller.clone());
 }
 
 // Test successful mutual cancellation (buyer proposes, seller agrees)
@@ -175,15 +209,14 @@ fn test_buyer_proposes_seller_agrees() {
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Buyer proposes cancellation
-    client.buyer_propose_cancellation(&tx_id);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
     // Record balances before cancellation agreement
-    let contract_address = env.current_contract_address();
+    let contract_address = client.address.clone();
     let buyer_balance_before = token_client.balance(&buyer);
     let contract_balance_before = token_client.balance(&contract_address);
     
     // Seller agrees to cancellation
-    env.set_invoker(seller.clone());
     client.agree_to_cancellation(&tx_id);
     
     // Verify transaction status is updated
@@ -197,12 +230,12 @@ fn test_buyer_proposes_seller_agrees() {
     assert_eq!(contract_balance_after, contract_balance_before - amount);
     
     // Verify event was emitted
-    let events = env.events().all();
+    let events = contract_events(&env, &client);
     assert_eq!(events.len(), 3); // Transaction creation + proposal + agreement
     
-    let topics = events[2].topics.clone();
-    assert_eq!(topics.get(0).unwrap(), Symbol::new(&env, "can_agree").into_val(&env));
-    assert_eq!(topics.get(1).unwrap(), tx_id.into_val(&env));
+    let (_, topics, _) = events.get(2).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "can_agree"));
+    assert_topic(&env, &topics, 1, tx_id);
 }
 
 // Test successful mutual cancellation (seller proposes, buyer agrees)
@@ -215,15 +248,14 @@ fn test_seller_proposes_buyer_agrees() {
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Seller proposes cancellation
-    client.seller_propose_cancellation(&tx_id);
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
     // Record balances before cancellation agreement
-    let contract_address = env.current_contract_address();
+    let contract_address = client.address.clone();
     let buyer_balance_before = token_client.balance(&buyer);
     let contract_balance_before = token_client.balance(&contract_address);
     
     // Buyer agrees to cancellation
-    env.set_invoker(buyer.clone());
     client.agree_to_cancellation(&tx_id);
     
     // Verify transaction status is updated
@@ -237,24 +269,24 @@ fn test_seller_proposes_buyer_agrees() {
     assert_eq!(contract_balance_after, contract_balance_before - amount);
     
     // Verify event was emitted
-    let events = env.events().all();
+    let events = contract_events(&env, &client);
     assert_eq!(events.len(), 3); // Transaction creation + proposal + agreement
     
-    let topics = events[2].topics.clone();
-    assert_eq!(topics.get(0).unwrap(), Symbol::new(&env, "can_agree").into_val(&env));
+    let (_, topics, _) = events.get(2).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "can_agree"));
 }
 
 // Test proposal expiration
 #[test]
 fn test_proposal_expires() {
-    let (mut env, client, buyer, seller, token_address, _token_client) = setup_test();
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
     
     // Create a transaction
     let amount = 1000;
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Buyer proposes cancellation
-    client.buyer_propose_cancellation(&tx_id);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
     // Advance ledger time beyond response window (7 days + 1 second)
     let response_window = client.get_response_window();
@@ -265,6 +297,7 @@ fn test_proposal_expires() {
     // Check if proposal has expired
     let is_expired = client.check_cancellation_expiry(&tx_id);
     assert!(is_expired);
+    assert_eq!(client.get_effective_status(&tx_id), CancellationStatus::Expired);
     
     // Reset expired proposal
     client.reset_expired_proposal(&tx_id);
@@ -274,16 +307,15 @@ fn test_proposal_expires() {
     assert_eq!(tx.status, CancellationStatus::None);
     
     // Verify event was emitted
-    let events = env.events().all();
+    let events = contract_events(&env, &client);
     assert_eq!(events.len(), 3); // Transaction creation + proposal + expiry
     
-    let topics = events[2].topics.clone();
-    assert_eq!(topics.get(0).unwrap(), Symbol::new(&env, "can_exp").into_val(&env));
+    let (_, topics, _) = events.get(2).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "can_exp"));
 }
 
 // Test that third party cannot propose cancellation (testing buyer proposal)
 #[test]
-#[should_panic(expected = "Unauthorized")] // Expect require_auth panic
 fn test_third_party_cannot_propose_buyer() {
     let (env, client, buyer, seller, token_address, _token_client) = setup_test();
     
@@ -291,15 +323,13 @@ fn test_third_party_cannot_propose_buyer() {
     let amount = 1000;
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
-    // Third party tries to propose cancellation as buyer
-    let third_party = Address::generate(&env);
-    env.set_invoker(third_party);
-    client.buyer_propose_cancellation(&tx_id);
+    // Proposing as buyer needs the buyer's own authorization, not a third party's
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    assert_authorized_by(&env, &buyer);
 }
 
 // Test that third party cannot propose cancellation (testing seller proposal)
 #[test]
-#[should_panic(expected = "Unauthorized")] // Expect require_auth panic
 fn test_third_party_cannot_propose_seller() {
     let (env, client, buyer, seller, token_address, _token_client) = setup_test();
     
@@ -307,15 +337,13 @@ fn test_third_party_cannot_propose_seller() {
     let amount = 1000;
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
-    // Third party tries to propose cancellation as seller
-    let third_party = Address::generate(&env);
-    env.set_invoker(third_party);
-    client.seller_propose_cancellation(&tx_id);
+    // Proposing as seller needs the seller's own authorization, not a third party's
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    assert_authorized_by(&env, &seller);
 }
 
 // Test that wrong party cannot agree to cancellation
 #[test]
-#[should_panic(expected = "Unauthorized")] // Expect require_auth panic
 fn test_wrong_party_cannot_agree() {
     let (env, client, buyer, seller, token_address, _token_client) = setup_test();
     
@@ -324,17 +352,15 @@ fn test_wrong_party_cannot_agree() {
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Buyer proposes cancellation
-    client.buyer_propose_cancellation(&tx_id);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
-    // Third party tries to agree (should require seller auth)
-    let third_party = Address::generate(&env);
-    env.set_invoker(third_party);
+    // Agreeing needs the seller's authorization, not a third party's
     client.agree_to_cancellation(&tx_id);
+    assert_authorized_by(&env, &seller);
 }
 
 // Test that buyer cannot agree to their own proposal
 #[test]
-#[should_panic(expected = "Unauthorized")] // Expect require_auth panic (needs seller auth)
 fn test_buyer_cannot_agree_to_own_proposal() {
     let (env, client, buyer, seller, token_address, _token_client) = setup_test();
     
@@ -343,15 +369,15 @@ fn test_buyer_cannot_agree_to_own_proposal() {
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Buyer proposes cancellation
-    client.buyer_propose_cancellation(&tx_id);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
-    // Buyer tries to agree to their own proposal (should require seller auth)
+    // Agreeing needs the seller's authorization, the buyer's own is not enough
     client.agree_to_cancellation(&tx_id);
+    assert_authorized_by(&env, &seller);
 }
 
 // Test that seller cannot agree to their own proposal
 #[test]
-#[should_panic(expected = "Unauthorized")] // Expect require_auth panic (needs buyer auth)
 fn test_seller_cannot_agree_to_own_proposal() {
     let (env, client, buyer, seller, token_address, _token_client) = setup_test();
     
@@ -360,24 +386,24 @@ fn test_seller_cannot_agree_to_own_proposal() {
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Seller proposes cancellation
-    client.seller_propose_cancellation(&tx_id);
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
-    // Seller tries to agree to their own proposal (should require buyer auth)
+    // Agreeing needs the buyer's authorization, the seller's own is not enough
     client.agree_to_cancellation(&tx_id);
+    assert_authorized_by(&env, &buyer);
 }
 
 // Test that cannot agree after proposal expiration
 #[test]
-#[should_panic(expected = "Cancellation proposal has expired")]
 fn test_cannot_agree_after_expiration() {
-    let (mut env, client, buyer, seller, token_address, _token_client) = setup_test();
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
     
     // Create a transaction
     let amount = 1000;
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Buyer proposes cancellation
-    client.buyer_propose_cancellation(&tx_id);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
     // Advance ledger time beyond response window
     let response_window = client.get_response_window();
@@ -386,21 +412,23 @@ fn test_cannot_agree_after_expiration() {
     });
     
     // Seller tries to agree after expiration
-    env.set_invoker(seller.clone());
-    client.agree_to_cancellation(&tx_id);
+    assert_eq!(
+        client.try_agree_to_cancellation(&tx_id),
+        Err(Ok(CancellationError::ProposalExpired))
+    );
 }
 
 // Test that new proposal can be made after previous one expires
 #[test]
 fn test_new_proposal_after_expiration() {
-    let (mut env, client, buyer, seller, token_address, _token_client) = setup_test();
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
     
     // Create a transaction
     let amount = 1000;
     let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
     
     // Buyer proposes cancellation
-    client.buyer_propose_cancellation(&tx_id);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
     // Advance ledger time beyond response window
     let response_window = client.get_response_window();
@@ -409,9 +437,1080 @@ fn test_new_proposal_after_expiration() {
     });
     
     // Seller makes a new proposal (should reset status internally if expired)
-    client.seller_propose_cancellation(&tx_id);
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
     
     // Verify transaction status is updated to seller's proposal
     let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
     assert_eq!(tx.status, CancellationStatus::ProposedBySeller);
-} 
\ No newline at end of file
+} 
+
+// Test buyer confirming receipt releases funds to the seller
+#[test]
+fn test_confirm_receipt() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Buyer confirms receipt
+    client.confirm_receipt(&tx_id);
+    
+    // Verify transaction status is updated
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::Released);
+    
+    // Verify funds are released to seller
+    assert_eq!(token_client.balance(&seller), amount);
+    
+    // Verify event was emitted
+    let events = contract_events(&env, &client);
+    assert_eq!(events.len(), 2); // Transaction creation + release
+    
+    let (_, topics, _) = events.get(1).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "released"));
+    assert_topic(&env, &topics, 1, tx_id);
+}
+
+// Test that seller cannot confirm receipt on the buyer's behalf
+#[test]
+fn test_seller_cannot_confirm_receipt() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Releasing the funds needs the buyer's authorization, the seller's is not enough
+    client.confirm_receipt(&tx_id);
+    assert_authorized_by(&env, &buyer);
+}
+
+// Test that cancellation cannot be proposed once funds are released
+#[test]
+fn test_cannot_propose_after_release() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Buyer confirms receipt
+    client.confirm_receipt(&tx_id);
+    
+    // Buyer tries to propose cancellation afterwards
+    assert_eq!(
+        client.try_buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount),
+        Err(Ok(CancellationError::AlreadyReleased))
+    );
+}
+
+// Test auto-release after the auto-release period passes
+#[test]
+fn test_auto_release() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Advance ledger time beyond auto-release period
+    let auto_release_period = client.get_auto_release_period();
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += auto_release_period;
+    });
+    
+    // Anyone can trigger the release
+    client.auto_release(&tx_id);
+    
+    // Verify transaction status is updated and funds are released to seller
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::Released);
+    assert_eq!(token_client.balance(&seller), amount);
+}
+
+// Test that auto-release is not possible before the period passes
+#[test]
+fn test_auto_release_too_early() {
+    let (_env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Try to release right away
+    assert_eq!(
+        client.try_auto_release(&tx_id),
+        Err(Ok(CancellationError::AutoReleaseNotDue))
+    );
+}
+
+// Test that an open cancellation proposal blocks auto-release
+#[test]
+fn test_auto_release_blocked_by_open_proposal() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Advance ledger time to just before the auto-release period ends
+    let auto_release_period = client.get_auto_release_period();
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += auto_release_period - 1;
+    });
+    
+    // Buyer proposes cancellation
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    
+    // Advance ledger time beyond auto-release period, within the response window
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += 1;
+    });
+    
+    // Try to release while the proposal is open
+    assert_eq!(
+        client.try_auto_release(&tx_id),
+        Err(Ok(CancellationError::ProposalOpen))
+    );
+}
+
+// Test partial refund (seller proposes keeping a restocking fee, buyer agrees)
+#[test]
+fn test_partial_refund() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Seller proposes refunding all but a restocking fee
+    let refund_amount = 800;
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &refund_amount);
+    
+    // Record balances before cancellation agreement
+    let buyer_balance_before = token_client.balance(&buyer);
+    
+    // Buyer agrees to cancellation
+    client.agree_to_cancellation(&tx_id);
+    
+    // Verify the split is recorded on the transaction
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::Completed);
+    assert_eq!(tx.refund_amount, refund_amount);
+    
+    // Verify funds are split between buyer and seller
+    assert_eq!(token_client.balance(&buyer), buyer_balance_before + refund_amount);
+    assert_eq!(token_client.balance(&seller), amount - refund_amount);
+    
+    // Verify event carries the split
+    let events = contract_events(&env, &client);
+    let (_, topics, data) = events.get(2).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "can_agree"));
+    let (buyer_amount, seller_amount, fee, compensation, items): (i128, i128, i128, i128, Vec<u64>) = data.into_val(&env);
+    assert_eq!((buyer_amount, seller_amount), (refund_amount, amount - refund_amount));
+    assert_eq!((fee, compensation), (0, 0));
+    assert_eq!(items, vec![&env, 1]);
+}
+
+// Test that a refund larger than the escrowed amount cannot be proposed
+#[test]
+fn test_refund_exceeding_amount() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Buyer proposes a refund larger than the escrowed amount
+    assert_eq!(
+        client.try_buyer_propose_cancellation(&tx_id, &Vec::new(&env), &(amount + 1)),
+        Err(Ok(CancellationError::InvalidRefundAmount))
+    );
+}
+
+// Test counter-proposal (buyer proposes full refund, seller counters, buyer agrees)
+#[test]
+fn test_counter_proposal() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Buyer proposes a full refund
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    
+    // Seller counters with a partial refund a day later
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += 24 * 60 * 60;
+    });
+    client.counter_propose(&tx_id, &Vec::new(&env), &900);
+    
+    // Verify the pending terms are replaced and the window restarted
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::ProposedBySeller);
+    assert_eq!(tx.refund_amount, 900);
+    assert_eq!(tx.proposal_timestamp, env.ledger().timestamp());
+    
+    // Buyer agrees to the counter-proposal
+    client.agree_to_cancellation(&tx_id);
+    assert_eq!(token_client.balance(&seller), 100);
+    
+    // Verify the negotiation history
+    let history = client.get_negotiation_history(&tx_id);
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.get(0).unwrap().action, NegotiationAction::Proposed);
+    assert_eq!(history.get(0).unwrap().party, buyer);
+    assert_eq!(history.get(0).unwrap().refund_amount, amount);
+    assert_eq!(history.get(1).unwrap().action, NegotiationAction::CounterProposed);
+    assert_eq!(history.get(1).unwrap().party, seller);
+    assert_eq!(history.get(1).unwrap().refund_amount, 900);
+    assert_eq!(history.get(2).unwrap().action, NegotiationAction::Agreed);
+    assert_eq!(history.get(2).unwrap().party, buyer);
+}
+
+// Test that the proposer cannot counter their own proposal
+#[test]
+fn test_proposer_cannot_counter_own_proposal() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Buyer proposes cancellation
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    
+    // Countering needs the seller's authorization, the buyer's own is not enough
+    client.counter_propose(&tx_id, &Vec::new(&env), &500);
+    assert_authorized_by(&env, &seller);
+    
+    // The counter-proposal is the seller's, so only the buyer can counter it
+    client.counter_propose(&tx_id, &Vec::new(&env), &700);
+    assert_authorized_by(&env, &buyer);
+}
+
+// Test proposer withdrawing their proposal
+#[test]
+fn test_withdraw_proposal() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    // Seller proposes cancellation and withdraws it
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    client.withdraw_proposal(&tx_id);
+    
+    // Verify transaction status is reset
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::None);
+    
+    // Verify event was emitted
+    let events = contract_events(&env, &client);
+    assert_eq!(events.len(), 3); // Transaction creation + proposal + withdrawal
+    
+    let (_, topics, _) = events.get(2).unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "can_wdraw"));
+    assert_topic(&env, &topics, 2, seller.clone());
+    
+    // Verify the negotiation history
+    let history = client.get_negotiation_history(&tx_id);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history.get(1).unwrap().action, NegotiationAction::Withdrawn);
+    
+    // Buyer can make a new proposal right away
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+}
+
+// Test moving records kept in instance storage by the first version to persistent storage, in batches
+#[test]
+fn test_migrate_storage() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Store two transactions the way the first version did, one with a pending proposal,
+    // along with their escrowed funds
+    let amount = 1000;
+    token::StellarAssetClient::new(&env, &token_address).mint(&client.address, &(2 * amount));
+    env.as_contract(&client.address, || {
+        for (id, status) in [(1, CancellationStatus::None), (2, CancellationStatus::ProposedByBuyer)] {
+            let legacy = LegacyTransaction {
+                id,
+                buyer: buyer.clone(),
+                seller: seller.clone(),
+                token: token_address.clone(),
+                amount,
+                status,
+                proposal_timestamp: env.ledger().timestamp(),
+                response_window: 7 * 24 * 60 * 60,
+            };
+            env.storage().instance().set(&DataKey::Transaction(id), &legacy);
+        }
+        let ids: Vec<u64> = vec![&env, 1, 2];
+        env.storage().instance().set(&DataKey::TransactionCounter, &2u64);
+        env.storage().instance().set(&DataKey::BuyerTransactions(buyer.clone()), &ids);
+        env.storage().instance().set(&DataKey::SellerTransactions(seller.clone()), &ids);
+    });
+    assert!(client.get_transaction(&1).is_none());
+    
+    // Admin migrates the first transaction
+    let admin = client.get_admin();
+    assert_eq!(client.migrate_storage(&1, &1), 2);
+    assert_authorized_by(&env, &admin);
+    assert_eq!(client.get_migration_cursor(), 2);
+    
+    // Verify the transaction was converted to the current layout
+    let tx = client.get_transaction(&1).expect("Transaction should exist");
+    assert_eq!(tx.amount, amount);
+    assert_eq!(tx.items.len(), 1);
+    assert_eq!(tx.items.get(0).unwrap().amount, amount);
+    assert_eq!(tx.status, CancellationStatus::None);
+    assert_eq!(tx.auto_release_period, 0);
+    assert!(client.get_transaction(&2).is_none());
+    
+    // Running the batch again is harmless, then the admin continues from the cursor
+    assert_eq!(client.migrate_storage(&1, &1), 2);
+    assert_eq!(client.migrate_storage(&client.get_migration_cursor(), &10), 3);
+    
+    // Verify the pending proposal, indexes and status counters
+    let tx = client.get_transaction(&2).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::ProposedByBuyer);
+    assert_eq!(tx.refund_amount, amount);
+    assert_eq!(tx.proposed_items, vec![&env, 1]);
+    assert_eq!(client.get_buyer_transactions(&buyer, &None, &0, &10).transactions.len(), 2);
+    assert_eq!(client.get_seller_transactions(&seller, &None, &0, &10).transactions.len(), 2);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::ProposedByBuyer), 1);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::None), 1);
+    
+    // Verify nothing is left in instance storage
+    env.as_contract(&client.address, || {
+        assert!(!env.storage().instance().has(&DataKey::Transaction(2)));
+        assert!(!env.storage().instance().has(&DataKey::BuyerTransactions(buyer.clone())));
+    });
+    
+    // The migrated proposal can be agreed to
+    client.agree_to_cancellation(&2);
+    assert_eq!(client.get_transaction(&2).unwrap().status, CancellationStatus::Completed);
+}
+
+// Test that the migration stops once every transaction ID was walked
+#[test]
+fn test_migrate_storage_done() {
+    let (_env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    create_test_transaction(&client, &buyer, &seller, &token_address, 1000);
+    assert_eq!(client.migrate_storage(&1, &10), 2);
+    assert_eq!(
+        client.try_migrate_storage(&2, &10),
+        Err(Ok(CancellationError::AlreadyMigrated))
+    );
+}
+
+// Test that the contract cannot be initialized twice
+#[test]
+fn test_initialize_once() {
+    let (env, client, _buyer, _seller, _token_address, _token_client) = setup_test();
+    
+    // Someone tries to take over the contract by initializing it again
+    let attacker = Address::generate(&env);
+    assert_eq!(
+        client.try_initialize(
+            &attacker,
+            &(24 * 60 * 60),
+            &WindowBounds { min: 0, max: 24 * 60 * 60 },
+            &0,
+        ),
+        Err(Ok(CancellationError::AlreadyInitialized))
+    );
+}
+
+// Test admin changing the default response window
+#[test]
+fn test_set_response_window() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Admin shortens the default response window to 3 days
+    let admin = client.get_admin();
+    client.set_response_window(&(3 * 24 * 60 * 60));
+    
+    // Verify the admin authorized the change
+    assert_authorized_by(&env, &admin);
+    assert_eq!(client.get_response_window(), 3 * 24 * 60 * 60);
+    
+    // Verify new transactions use the new default
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.response_window, 3 * 24 * 60 * 60);
+}
+
+// Test that the default response window must stay within the bounds
+#[test]
+fn test_set_response_window_out_of_bounds() {
+    let (_env, client, _buyer, _seller, _token_address, _token_client) = setup_test();
+    
+    // Admin tries to set a window longer than the 30-day maximum
+    assert_eq!(
+        client.try_set_response_window(&(31 * 24 * 60 * 60)),
+        Err(Ok(CancellationError::WindowOutOfBounds))
+    );
+}
+
+// Test buyer choosing a per-transaction response window
+#[test]
+fn test_per_transaction_response_window() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction with a 2-day response window
+    let amount = 1000;
+    let tx_id = client.create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, amount)], &Some(2 * 24 * 60 * 60), &None);
+    
+    // Verify the transaction uses its own window
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.response_window, 2 * 24 * 60 * 60);
+}
+
+// Test that a per-transaction response window must be within the bounds
+#[test]
+fn test_per_transaction_response_window_out_of_bounds() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Buyer tries to create a transaction with a 1-minute response window
+    let amount = 1000;
+    assert_eq!(
+        client.try_create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, amount)], &Some(60), &None),
+        Err(Ok(CancellationError::WindowOutOfBounds))
+    );
+}
+
+// Test that no transaction can be created before the contract is initialized
+#[test]
+fn test_create_transaction_before_initialize() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (token_address, token_client) = create_token_contract(&env, &admin);
+    let buyer = create_user(&env, &token_client, 10000);
+    let seller = Address::generate(&env);
+    let contract_id = env.register_contract(None, MutualCancellation);
+    let client = MutualCancellationClient::new(&env, &contract_id);
+    
+    // Without the window bounds any response window would be accepted
+    assert_eq!(
+        client.try_create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, 1000)], &Some(u64::MAX), &None),
+        Err(Ok(CancellationError::NotInitialized))
+    );
+}
+
+// Test that a response window reaching past the largest timestamp never expires
+#[test]
+fn test_unbounded_response_window() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Admin lifts the upper bound and the buyer picks the longest window
+    client.set_response_window_bounds(&WindowBounds { min: 24 * 60 * 60, max: u64::MAX });
+    let amount = 1000;
+    let tx_id = client.create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, amount)], &Some(u64::MAX), &None);
+    
+    // Buyer proposes cancellation, a year later it is still pending
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += 365 * 24 * 60 * 60;
+    });
+    assert_eq!(client.get_effective_status(&tx_id), CancellationStatus::ProposedByBuyer);
+    assert_eq!(
+        client.try_reset_expired_proposal(&tx_id),
+        Err(Ok(CancellationError::ProposalNotExpired))
+    );
+    
+    // Seller can still agree
+    client.agree_to_cancellation(&tx_id);
+    assert_eq!(token_client.balance(&buyer), 10000);
+}
+
+// Test that the effective status reports an expired proposal before it is reset
+#[test]
+fn test_effective_status() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    assert_eq!(client.get_effective_status(&tx_id), CancellationStatus::None);
+    
+    // Seller proposes cancellation
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    assert_eq!(client.get_effective_status(&tx_id), CancellationStatus::ProposedBySeller);
+    
+    // Advance ledger time beyond response window
+    let response_window = client.get_response_window();
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += response_window + 1;
+    });
+    
+    // Verify the proposal reads as expired while the stored status is unchanged
+    assert_eq!(client.get_effective_status(&tx_id), CancellationStatus::Expired);
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::ProposedBySeller);
+    
+    // Verify an unknown transaction is reported as an error
+    assert_eq!(
+        client.try_get_effective_status(&(tx_id + 1)),
+        Err(Ok(CancellationError::TransactionNotFound))
+    );
+}
+
+// Test escalating after repeated expired proposals and a split ruling by the default arbiter
+#[test]
+fn test_escalation_split_ruling() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Admin sets a default arbiter and allows escalation after 2 expired proposals
+    let arbiter = Address::generate(&env);
+    client.set_default_arbiter(&Some(arbiter.clone()));
+    client.set_escalation_policy(&EscalationPolicy { max_expired_proposals: 2, max_age: 0 });
+    
+    // Create a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    assert_eq!(client.get_arbiter(&tx_id), Some(arbiter.clone()));
+    
+    // Seller ignores the buyer's first proposal
+    let response_window = client.get_response_window();
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += response_window + 1;
+    });
+    
+    // One expired proposal is not enough to escalate
+    assert_eq!(
+        client.try_escalate_to_arbiter(&tx_id, &buyer),
+        Err(Ok(CancellationError::EscalationNotDue))
+    );
+    
+    // Seller ignores the second proposal too
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += response_window + 1;
+    });
+    
+    // Buyer escalates
+    client.escalate_to_arbiter(&tx_id, &buyer);
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::Escalated);
+    assert_eq!(tx.expired_proposals, 2);
+    
+    // Verify the transaction is frozen until the ruling
+    assert_eq!(
+        client.try_seller_propose_cancellation(&tx_id, &Vec::new(&env), &amount),
+        Err(Ok(CancellationError::AlreadyEscalated))
+    );
+    
+    // Arbiter refunds 400 to the buyer and pays the rest to the seller
+    let initial_buyer_balance = token_client.balance(&buyer);
+    client.rule_on_escalation(&tx_id, &Ruling::Split(400));
+    assert_eq!(token_client.balance(&buyer), initial_buyer_balance + 400);
+    assert_eq!(token_client.balance(&seller), 600);
+    
+    // Verify nothing is left in escrow
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::Completed);
+    assert_eq!(tx.refund_amount, 400);
+    assert_eq!(tx.amount, 0);
+    assert!(tx.items.get(0).unwrap().cancelled);
+    assert_eq!(token_client.balance(&client.address), 0);
+    
+    // Verify the ruling was recorded and announced
+    let history = client.get_negotiation_history(&tx_id);
+    assert_eq!(history.last().unwrap().action, NegotiationAction::Ruled);
+    assert_eq!(history.last().unwrap().party, arbiter);
+    let events = contract_events(&env, &client);
+    let (_, topics, data) = events.last().unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "arb_ruled"));
+    let (buyer_amount, seller_amount): (i128, i128) = data.into_val(&env);
+    assert_eq!((buyer_amount, seller_amount), (400, 600));
+}
+
+// Test escalating an old transaction to its own arbiter, who releases the funds to the seller
+#[test]
+fn test_escalation_by_age_release_ruling() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Admin allows escalation 60 days after creation
+    client.set_escalation_policy(&EscalationPolicy { max_expired_proposals: 0, max_age: 60 * 24 * 60 * 60 });
+    
+    // Create a transaction with its own arbiter
+    let amount = 1000;
+    let arbiter = Address::generate(&env);
+    let tx_id = client.create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, amount)], &None, &Some(arbiter.clone()));
+    
+    // A third party cannot escalate
+    assert_eq!(
+        client.try_escalate_to_arbiter(&tx_id, &arbiter),
+        Err(Ok(CancellationError::NotAParty))
+    );
+    
+    // Seller escalates once the transaction is old enough
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += 60 * 24 * 60 * 60;
+    });
+    client.escalate_to_arbiter(&tx_id, &seller);
+    
+    // Arbiter releases the funds to the seller
+    client.rule_on_escalation(&tx_id, &Ruling::Release);
+    assert_eq!(token_client.balance(&seller), amount);
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::Released);
+}
+
+// Test that a buyer choosing their own arbiter needs the seller's authorization
+#[test]
+fn test_custom_arbiter_needs_seller() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction with its own arbiter
+    let arbiter = Address::generate(&env);
+    let tx_id = client.create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, 1000)], &None, &Some(arbiter.clone()));
+    
+    // Verify both parties authorized the transaction
+    let auths = env.auths();
+    assert_eq!(auths.len(), 2);
+    assert_eq!(auths[0].0, buyer);
+    assert_eq!(auths[1].0, seller);
+    assert_eq!(client.get_arbiter(&tx_id), Some(arbiter));
+    
+    // Neither party can be the arbiter
+    assert_eq!(
+        client.try_create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, 1000)], &None, &Some(buyer.clone())),
+        Err(Ok(CancellationError::InvalidArbiter))
+    );
+    assert_eq!(
+        client.try_create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, 1000)], &None, &Some(seller.clone())),
+        Err(Ok(CancellationError::InvalidArbiter))
+    );
+}
+
+// Test that the default arbiter only needs the buyer's authorization, unless it is a party
+#[test]
+fn test_default_arbiter() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Admin sets a default arbiter
+    let arbiter = Address::generate(&env);
+    client.set_default_arbiter(&Some(arbiter.clone()));
+    
+    // Create a transaction with the default arbiter
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, 1000);
+    assert_authorized_by(&env, &buyer);
+    assert_eq!(client.get_arbiter(&tx_id), Some(arbiter));
+    
+    // A default arbiter can't arbitrate their own purchases
+    client.set_default_arbiter(&Some(buyer.clone()));
+    assert_eq!(
+        client.try_create_transaction(&buyer, &seller, &token_address, &vec![&env, (1, 1000)], &None, &None),
+        Err(Ok(CancellationError::InvalidArbiter))
+    );
+}
+
+// Test that a transaction without an arbiter cannot be escalated
+#[test]
+fn test_escalation_without_arbiter() {
+    let (_env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    client.set_escalation_policy(&EscalationPolicy { max_expired_proposals: 0, max_age: 1 });
+    
+    // Create a transaction without an arbiter
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    
+    assert_eq!(
+        client.try_escalate_to_arbiter(&tx_id, &buyer),
+        Err(Ok(CancellationError::NoArbiter))
+    );
+}
+
+// Test cancelling some line items of an order and releasing the rest
+#[test]
+fn test_line_item_cancellation() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Create a transaction with three line items
+    let items = vec![&env, (1, 300), (2, 500), (3, 200)];
+    let tx_id = client.create_transaction(&buyer, &seller, &token_address, &items, &None, &None);
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.amount, 1000);
+    assert_eq!(tx.items.len(), 3);
+    
+    // Buyer proposes cancelling items 1 and 3, seller keeps 50 as a restocking fee
+    let initial_buyer_balance = token_client.balance(&buyer);
+    client.buyer_propose_cancellation(&tx_id, &vec![&env, 1, 3], &450);
+    client.agree_to_cancellation(&tx_id);
+    
+    // Verify only the cancelled items were paid out
+    assert_eq!(token_client.balance(&buyer), initial_buyer_balance + 450);
+    assert_eq!(token_client.balance(&seller), 50);
+    
+    // Verify the rest of the order stays live
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::None);
+    assert_eq!(tx.amount, 500);
+    assert!(tx.items.get(0).unwrap().cancelled);
+    assert!(!tx.items.get(1).unwrap().cancelled);
+    assert!(tx.items.get(2).unwrap().cancelled);
+    
+    // Cancelled items cannot be proposed again
+    assert_eq!(
+        client.try_seller_propose_cancellation(&tx_id, &vec![&env, 1], &100),
+        Err(Ok(CancellationError::InvalidItems))
+    );
+    
+    // Buyer confirms receipt of the remaining item
+    client.confirm_receipt(&tx_id);
+    assert_eq!(token_client.balance(&seller), 550);
+}
+
+// Test that a proposal without items cancels every remaining item
+#[test]
+fn test_cancel_remaining_items() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction with two line items and cancel the first one
+    let items = vec![&env, (1, 300), (2, 700)];
+    let tx_id = client.create_transaction(&buyer, &seller, &token_address, &items, &None, &None);
+    client.seller_propose_cancellation(&tx_id, &vec![&env, 1], &300);
+    client.agree_to_cancellation(&tx_id);
+    
+    // Seller proposes cancelling the rest of the order
+    client.seller_propose_cancellation(&tx_id, &Vec::new(&env), &700);
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.proposed_items, vec![&env, 2]);
+    client.agree_to_cancellation(&tx_id);
+    
+    // Verify the whole order is cancelled
+    let tx = client.get_transaction(&tx_id).expect("Transaction should exist");
+    assert_eq!(tx.status, CancellationStatus::Completed);
+    assert_eq!(tx.amount, 0);
+}
+
+// Test that line items must have distinct IDs
+#[test]
+fn test_duplicate_line_items() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    let items = vec![&env, (1, 300), (1, 700)];
+    assert_eq!(
+        client.try_create_transaction(&buyer, &seller, &token_address, &items, &None, &None),
+        Err(Ok(CancellationError::InvalidItems))
+    );
+}
+
+// Test paging through a seller's transactions and filtering them by status
+#[test]
+fn test_paged_transaction_queries() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create five transactions, buyer proposes cancelling two of them
+    let mut tx_ids = Vec::new(&env);
+    for _ in 0..5 {
+        tx_ids.push_back(create_test_transaction(&client, &buyer, &seller, &token_address, 100));
+    }
+    client.buyer_propose_cancellation(&tx_ids.get(1).unwrap(), &Vec::new(&env), &100);
+    client.buyer_propose_cancellation(&tx_ids.get(3).unwrap(), &Vec::new(&env), &100);
+    
+    // Page through the seller's transactions, oldest first
+    let page = client.get_seller_transactions(&seller, &None, &0, &2);
+    assert_eq!(page.transactions.len(), 2);
+    assert_eq!(page.transactions.get(0).unwrap().id, tx_ids.get(0).unwrap());
+    assert_eq!(page.next_offset, 2);
+    let page = client.get_seller_transactions(&seller, &None, &4, &2);
+    assert_eq!(page.transactions.len(), 1);
+    assert_eq!(page.transactions.get(0).unwrap().id, tx_ids.get(4).unwrap());
+    assert_eq!(page.next_offset, 5);
+    
+    // The status filter fills the page with matches from further on
+    let pending = client.get_seller_transactions(&seller, &Some(CancellationStatus::ProposedByBuyer), &0, &1);
+    assert_eq!(pending.transactions.len(), 1);
+    assert_eq!(pending.transactions.get(0).unwrap().id, tx_ids.get(1).unwrap());
+    assert_eq!(pending.next_offset, 2);
+    let pending = client.get_seller_transactions(&seller, &Some(CancellationStatus::ProposedByBuyer), &pending.next_offset, &1);
+    assert_eq!(pending.transactions.len(), 1);
+    assert_eq!(pending.transactions.get(0).unwrap().id, tx_ids.get(3).unwrap());
+    assert_eq!(pending.next_offset, 4);
+    let pending = client.get_seller_transactions(&seller, &Some(CancellationStatus::ProposedByBuyer), &pending.next_offset, &1);
+    assert_eq!(pending.transactions.len(), 0);
+    assert_eq!(pending.next_offset, 5);
+    
+    // Expired proposals are filtered by their effective status
+    let response_window = client.get_response_window();
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += response_window + 1;
+    });
+    let expired = client.get_buyer_transactions(&buyer, &Some(CancellationStatus::Expired), &0, &5);
+    assert_eq!(expired.transactions.len(), 2);
+}
+
+// Test that a filtered page reads at most `MAX_PAGE_SIZE` transactions
+#[test]
+fn test_paged_transaction_queries_read_cap() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create more transactions than one page reads, proposing to cancel only the last one
+    let mut last_id = 0;
+    for _ in 0..MAX_PAGE_SIZE + 1 {
+        last_id = create_test_transaction(&client, &buyer, &seller, &token_address, 100);
+    }
+    client.buyer_propose_cancellation(&last_id, &Vec::new(&env), &100);
+    
+    // The first page comes back empty and the next one finds the proposal
+    let status = Some(CancellationStatus::ProposedByBuyer);
+    let page = client.get_seller_transactions(&seller, &status, &0, &10);
+    assert_eq!(page.transactions.len(), 0);
+    assert_eq!(page.next_offset, MAX_PAGE_SIZE);
+    let page = client.get_seller_transactions(&seller, &status, &page.next_offset, &10);
+    assert_eq!(page.transactions.len(), 1);
+    assert_eq!(page.transactions.get(0).unwrap().id, last_id);
+    assert_eq!(page.next_offset, MAX_PAGE_SIZE + 1);
+}
+
+// Test the per-party status counters
+#[test]
+fn test_status_counts() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create three transactions
+    let amount = 1000;
+    let tx_1 = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    let tx_2 = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    assert_eq!(client.get_buyer_status_count(&buyer, &CancellationStatus::None), 3);
+    
+    // Buyer proposes cancelling one and confirms receipt of another
+    client.buyer_propose_cancellation(&tx_1, &Vec::new(&env), &amount);
+    client.confirm_receipt(&tx_2);
+    
+    // Verify the seller's pending cancellation requests
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::ProposedByBuyer), 1);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::Released), 1);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::None), 1);
+    
+    // Seller agrees and the request is no longer pending
+    client.agree_to_cancellation(&tx_1);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::ProposedByBuyer), 0);
+    assert_eq!(client.get_buyer_status_count(&buyer, &CancellationStatus::Completed), 1);
+}
+
+// Test that expired proposals are not counted as pending
+#[test]
+fn test_status_counts_expired() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Buyer proposes cancelling a transaction
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::ProposedByBuyer), 1);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::Expired), 0);
+    
+    // Once the response window has passed the proposal is counted as expired
+    let response_window = client.get_response_window();
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += response_window + 1;
+    });
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::ProposedByBuyer), 0);
+    assert_eq!(client.get_buyer_status_count(&buyer, &CancellationStatus::Expired), 1);
+    
+    // Proposing again restarts the response window
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::ProposedByBuyer), 1);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::Expired), 0);
+    
+    // Resetting an expired proposal drops it from both counts
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += response_window + 1;
+    });
+    client.reset_expired_proposal(&tx_id);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::ProposedByBuyer), 0);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::Expired), 0);
+    assert_eq!(client.get_seller_status_count(&seller, &CancellationStatus::None), 1);
+}
+
+// Test the fee a buyer pays for cancelling a shipped order
+#[test]
+fn test_shipped_cancellation_fee() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Admin charges buyers 10% for cancelling shipped orders
+    client.set_penalty_policy(&PenaltyPolicy { shipped_fee_bps: 1000, seller_compensation_bps: 0 });
+    
+    // Create a transaction and ship it
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.mark_shipped(&tx_id);
+    assert!(client.get_transaction(&tx_id).unwrap().shipped);
+    
+    // Buyer cancels for a full refund, seller agrees
+    let initial_buyer_balance = token_client.balance(&buyer);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    client.agree_to_cancellation(&tx_id);
+    
+    // Verify the fee was paid to the seller out of the refund
+    assert_eq!(token_client.balance(&buyer), initial_buyer_balance + 900);
+    assert_eq!(token_client.balance(&seller), 100);
+    
+    // Verify the event reports the amounts
+    let events = contract_events(&env, &client);
+    let (_, topics, data) = events.last().unwrap();
+    assert_topic(&env, &topics, 0, Symbol::new(&env, "can_agree"));
+    let (buyer_amount, seller_amount, fee, compensation, _items): (i128, i128, i128, i128, Vec<u64>) = data.into_val(&env);
+    assert_eq!((buyer_amount, seller_amount, fee, compensation), (900, 100, 100, 0));
+}
+
+// Test that the fee is charged to the buyer who started the cancellation, even when the
+// agreed terms are the seller's counter-proposal
+#[test]
+fn test_shipped_cancellation_fee_after_counter() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Admin charges buyers 10% for cancelling shipped orders
+    client.set_penalty_policy(&PenaltyPolicy { shipped_fee_bps: 1000, seller_compensation_bps: 0 });
+    
+    // Create a transaction and ship it
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.mark_shipped(&tx_id);
+    
+    // Buyer cancels, seller counters with a smaller refund and the buyer agrees
+    let initial_buyer_balance = token_client.balance(&buyer);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    client.counter_propose(&tx_id, &Vec::new(&env), &800);
+    let transaction = client.get_transaction(&tx_id).unwrap();
+    assert_eq!(transaction.status, CancellationStatus::ProposedBySeller);
+    assert_eq!(transaction.initiator, CancellationStatus::ProposedByBuyer);
+    client.agree_to_cancellation(&tx_id);
+    
+    // Verify the buyer still paid the fee out of the countered refund
+    assert_eq!(token_client.balance(&buyer), initial_buyer_balance + 700);
+    assert_eq!(token_client.balance(&seller), 300);
+    assert_eq!(client.get_transaction(&tx_id).unwrap().initiator, CancellationStatus::None);
+    
+    let events = contract_events(&env, &client);
+    let (_, _topics, data) = events.last().unwrap();
+    let (buyer_amount, seller_amount, fee, compensation, _items): (i128, i128, i128, i128, Vec<u64>) = data.into_val(&env);
+    assert_eq!((buyer_amount, seller_amount, fee, compensation), (700, 300, 100, 0));
+}
+
+// Test that an order can't be marked as shipped while a cancellation is pending
+#[test]
+fn test_cannot_ship_with_pending_proposal() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Create a transaction and propose cancellation
+    let amount = 1000;
+    let tx_id = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.buyer_propose_cancellation(&tx_id, &Vec::new(&env), &amount);
+    
+    // Seller tries to ship to make the buyer pay a fee
+    assert_eq!(
+        client.try_mark_shipped(&tx_id),
+        Err(Ok(CancellationError::ProposalOpen))
+    );
+}
+
+// Test compensation paid from the seller bond for a seller-initiated cancellation
+#[test]
+fn test_seller_cancellation_compensation() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Admin has sellers compensate buyers 5% for cancelling
+    client.set_penalty_policy(&PenaltyPolicy { shipped_fee_bps: 0, seller_compensation_bps: 500 });
+    
+    // Seller gets paid for an earlier order and deposits part of it as bond
+    let amount = 1000;
+    let tx_1 = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.confirm_receipt(&tx_1);
+    client.deposit_bond(&seller, &token_address, &200);
+    assert_eq!(client.get_seller_bond(&seller, &token_address), 200);
+    
+    // Seller cancels the next order with a full refund
+    let tx_2 = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    let initial_buyer_balance = token_client.balance(&buyer);
+    client.seller_propose_cancellation(&tx_2, &Vec::new(&env), &amount);
+    client.agree_to_cancellation(&tx_2);
+    
+    // Verify the buyer received the refund and the compensation from the bond
+    assert_eq!(token_client.balance(&buyer), initial_buyer_balance + amount + 50);
+    assert_eq!(client.get_seller_bond(&seller, &token_address), 150);
+    
+    // Seller withdraws the rest of the bond
+    client.withdraw_bond(&seller, &token_address, &150);
+    assert_eq!(token_client.balance(&seller), 950);
+    assert_eq!(
+        client.try_withdraw_bond(&seller, &token_address, &1),
+        Err(Ok(CancellationError::InsufficientBond))
+    );
+}
+
+// Test that a seller cannot withdraw the bond owed on their pending cancellation proposal
+#[test]
+fn test_withdraw_bond_reserved_by_proposal() {
+    let (env, client, buyer, seller, token_address, token_client) = setup_test();
+    
+    // Admin has sellers compensate buyers 5% for cancelling
+    client.set_penalty_policy(&PenaltyPolicy { shipped_fee_bps: 0, seller_compensation_bps: 500 });
+    
+    // Seller gets paid for an earlier order and deposits part of it as bond
+    let amount = 1000;
+    let tx_1 = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.confirm_receipt(&tx_1);
+    client.deposit_bond(&seller, &token_address, &200);
+    
+    // Seller proposes cancelling the next order, reserving 50 of the bond
+    let tx_2 = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.seller_propose_cancellation(&tx_2, &Vec::new(&env), &amount);
+    assert_eq!(
+        client.try_withdraw_bond(&seller, &token_address, &200),
+        Err(Ok(CancellationError::InsufficientBond))
+    );
+    client.withdraw_bond(&seller, &token_address, &150);
+    
+    // Buyer agrees and still receives the compensation
+    let initial_buyer_balance = token_client.balance(&buyer);
+    client.agree_to_cancellation(&tx_2);
+    assert_eq!(token_client.balance(&buyer), initial_buyer_balance + amount + 50);
+    assert_eq!(client.get_seller_bond(&seller, &token_address), 0);
+}
+
+// Test that the bond reserved by a seller's proposal is released once it expires
+#[test]
+fn test_withdraw_bond_after_proposal_expired() {
+    let (env, client, buyer, seller, token_address, _token_client) = setup_test();
+    
+    // Admin has sellers compensate buyers 5% for cancelling
+    client.set_penalty_policy(&PenaltyPolicy { shipped_fee_bps: 0, seller_compensation_bps: 500 });
+    
+    // Seller deposits a bond and proposes cancelling an order
+    let amount = 1000;
+    let tx_1 = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.confirm_receipt(&tx_1);
+    client.deposit_bond(&seller, &token_address, &200);
+    let tx_2 = create_test_transaction(&client, &buyer, &seller, &token_address, amount);
+    client.seller_propose_cancellation(&tx_2, &Vec::new(&env), &amount);
+    
+    // Buyer counters, the seller still started the cancellation and owes the compensation
+    client.counter_propose(&tx_2, &Vec::new(&env), &amount);
+    assert_eq!(
+        client.try_withdraw_bond(&seller, &token_address, &200),
+        Err(Ok(CancellationError::InsufficientBond))
+    );
+    
+    // Once the proposal expires the whole bond can be withdrawn
+    let response_window = client.get_response_window();
+    env.ledger().with_mut(|li: &mut LedgerInfo| {
+        li.timestamp += response_window + 1;
+    });
+    client.withdraw_bond(&seller, &token_address, &200);
+    assert_eq!(client.get_seller_bond(&seller, &token_address), 0);
+}
+
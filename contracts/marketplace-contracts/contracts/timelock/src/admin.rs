@@ -0,0 +1,23 @@
+use soroban_sdk::{Address, Env};
+
+use crate::storage_types::DataKey;
+
+pub fn read_admin(env: &Env) -> Address {
+    let key = DataKey::Admin;
+    env.storage().instance().get(&key).unwrap()
+}
+
+pub fn write_admin(env: &Env, admin: &Address) {
+    let key = DataKey::Admin;
+    env.storage().instance().set(&key, admin);
+}
+
+pub fn is_paused(env: &Env) -> bool {
+    let key = DataKey::Paused;
+    env.storage().instance().get(&key).unwrap_or(false)
+}
+
+pub fn write_paused(env: &Env, paused: bool) {
+    let key = DataKey::Paused;
+    env.storage().instance().set(&key, &paused);
+}
@@ -1,6 +1,6 @@
 use soroban_sdk::Env;
 
-use crate::storage_types::DataKey;
+use crate::storage_types::{DataKey, DurationBounds};
 
 pub fn read_clawback_delay(env: &Env) -> u64 {
     let key = DataKey::ClawbackDelay;
@@ -11,3 +11,13 @@ pub fn write_clawback_delay(env: &Env, clawback_delay: u64) {
     let key = DataKey::ClawbackDelay;
     env.storage().instance().set(&key, &clawback_delay);
 }
+
+pub fn read_clawback_delay_bounds(env: &Env) -> DurationBounds {
+    let key = DataKey::ClawbackDelayBounds;
+    env.storage().instance().get(&key).unwrap()
+}
+
+pub fn write_clawback_delay_bounds(env: &Env, bounds: &DurationBounds) {
+    let key = DataKey::ClawbackDelayBounds;
+    env.storage().instance().set(&key, bounds);
+}
@@ -1,18 +1,114 @@
-use crate::clawback_delay::{read_clawback_delay, write_clawback_delay};
-use crate::deposit::{read_deposit, remove_deposit, write_deposit, Deposit};
+use crate::admin::{is_paused, read_admin, write_admin, write_paused};
+use crate::clawback_delay::{
+    read_clawback_delay, read_clawback_delay_bounds, write_clawback_delay,
+    write_clawback_delay_bounds,
+};
+use crate::deposit::{
+    extend_deposit_ttl, next_deposit_id, read_deposit, read_legacy_deposit, remove_deposit,
+    remove_legacy_deposit, write_deposit, Deposit, LegacyDeposit, Share, TopUpPolicy, UnlockClock,
+    BPS_DENOMINATOR,
+};
+use crate::deposit_index::{
+    add_deposit_to_indexes, extend_index_ttls, move_withdrawer_index, read_depositor_deposits,
+    read_withdrawer_deposits, remove_beneficiary_index,
+};
+use crate::error::TimelockError;
 use crate::events::Events;
-use crate::lock_duration::{read_lock_duration, write_lock_duration};
-use crate::storage_types::{INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD};
-use soroban_sdk::{contract, contractimpl, token, Address, Env};
+use crate::lock_duration::{
+    read_lock_duration, read_lock_duration_bounds, write_lock_duration, write_lock_duration_bounds,
+};
+use crate::storage_types::{
+    DurationBounds, INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD, LEDGER_CLOSE_SECONDS,
+    MAX_BATCH_SIZE, MAX_PAGE_SIZE, MAX_SHARES,
+};
+use crate::vesting::VestingSchedule;
+use soroban_sdk::{contract, contractimpl, panic_with_error, token, Address, BytesN, Env, Vec};
 
 #[contract]
 pub struct Timelock;
 
 #[contractimpl]
 impl Timelock {
-    pub fn __constructor(env: Env, lock_duration: u64, clawback_delay: u64) {
+    /// `lock_duration` and `clawback_delay` are the defaults used when a deposit does not
+    /// choose its own values; every value, default or chosen, must lie within the bounds.
+    pub fn __constructor(
+        env: Env,
+        admin: Address,
+        lock_duration: u64,
+        clawback_delay: u64,
+        lock_duration_bounds: DurationBounds,
+        clawback_delay_bounds: DurationBounds,
+    ) {
+        if !lock_duration_bounds.contains(lock_duration)
+            || !clawback_delay_bounds.contains(clawback_delay)
+        {
+            panic_with_error!(&env, TimelockError::InvalidDuration);
+        }
+        write_admin(&env, &admin);
         write_lock_duration(&env, lock_duration);
         write_clawback_delay(&env, clawback_delay);
+        write_lock_duration_bounds(&env, &lock_duration_bounds);
+        write_clawback_delay_bounds(&env, &clawback_delay_bounds);
+    }
+
+    pub fn admin(env: Env) -> Address {
+        read_admin(&env)
+    }
+
+    pub fn set_admin(env: Env, new_admin: Address) -> Result<(), TimelockError> {
+        let admin = read_admin(&env);
+        admin.require_auth();
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        write_admin(&env, &new_admin);
+        Events::new(&env).set_admin(admin, new_admin);
+        Ok(())
+    }
+
+    /// Blocks new deposits and top-ups. Withdrawals and clawbacks keep working so that
+    /// funds can always leave the contract.
+    pub fn pause(env: Env) -> Result<(), TimelockError> {
+        set_paused(&env, true)
+    }
+
+    pub fn unpause(env: Env) -> Result<(), TimelockError> {
+        set_paused(&env, false)
+    }
+
+    pub fn is_paused(env: Env) -> bool {
+        is_paused(&env)
+    }
+
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), TimelockError> {
+        let admin = read_admin(&env);
+        admin.require_auth();
+        env.deployer()
+            .update_current_contract_wasm(new_wasm_hash.clone());
+        Events::new(&env).upgrade(admin, new_wasm_hash);
+        Ok(())
+    }
+
+    /// Changes the default lock duration. Existing deposits keep the lock duration they
+    /// were created with.
+    pub fn set_lock_duration(env: Env, lock_duration: u64) -> Result<(), TimelockError> {
+        read_admin(&env).require_auth();
+        if !read_lock_duration_bounds(&env).contains(lock_duration) {
+            return Err(TimelockError::InvalidDuration);
+        }
+        write_lock_duration(&env, lock_duration);
+        Ok(())
+    }
+
+    /// Changes the default clawback delay. Existing deposits keep the clawback delay they
+    /// were created with.
+    pub fn set_clawback_delay(env: Env, clawback_delay: u64) -> Result<(), TimelockError> {
+        read_admin(&env).require_auth();
+        if !read_clawback_delay_bounds(&env).contains(clawback_delay) {
+            return Err(TimelockError::InvalidDuration);
+        }
+        write_clawback_delay(&env, clawback_delay);
+        Ok(())
     }
 
     pub fn lock_duration(env: Env) -> u64 {
@@ -23,112 +119,751 @@ impl Timelock {
         read_clawback_delay(&env)
     }
 
+    pub fn lock_duration_bounds(env: Env) -> DurationBounds {
+        read_lock_duration_bounds(&env)
+    }
+
+    pub fn clawback_delay_bounds(env: Env) -> DurationBounds {
+        read_clawback_delay_bounds(&env)
+    }
+
     pub fn deposit(
         env: Env,
         depositor: Address,
         withdrawer: Address,
         token: Address,
         amount: i128,
-    ) {
+        lock_duration: Option<u64>,
+        clawback_delay: Option<u64>,
+    ) -> Result<u64, TimelockError> {
         // Make sure `depositor` address authorized the deposit call with all the arguments.
         depositor.require_auth();
-        if let Some(deposit) = read_deposit(&env, &depositor, &withdrawer, &token) {
-            panic!(
-                "TimelockDuplicateDeposit({:?}, {:?}, {:?})",
-                deposit.depositor, deposit.withdrawer, deposit.token
-            );
+        if amount <= 0 {
+            return Err(TimelockError::InvalidAmount);
         }
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        let lock_duration = lock_duration.unwrap_or_else(|| read_lock_duration(&env));
+        let unlock_timestamp = unlock_after(&env, lock_duration)?;
+        let clawback_delay = check_new_deposit(&env, unlock_timestamp, clawback_delay)?;
         // Transfer token from `depositor` to this contract address.
         token::Client::new(&env, &token).transfer(
             &depositor,
             &env.current_contract_address(),
             &amount,
         );
-        // Store all the necessary info to allow the withdrawer to claim it.
-        let deposit = Deposit {
+        Ok(create_deposit(
+            &env,
             depositor,
             withdrawer,
             token,
             amount,
-            unlock_timestamp: env.ledger().timestamp() + read_lock_duration(&env),
-        };
-        write_deposit(&env, &deposit);
-        Events::new(&env).deposit(deposit);
+            VestingSchedule::Cliff,
+            UnlockClock::Timestamp,
+            unlock_timestamp,
+            clawback_delay,
+            Vec::new(&env),
+        ))
     }
 
-    pub fn get_deposit(
+    /// Same as `deposit`, but the funds unlock once the ledger sequence has advanced by
+    /// `lock_ledgers` rather than after a lock duration in seconds. The clawback delay is
+    /// counted in ledgers too. Both are checked against the configured duration bounds,
+    /// converted at the nominal ledger close time.
+    pub fn deposit_by_ledger(
         env: Env,
         depositor: Address,
         withdrawer: Address,
         token: Address,
-    ) -> Option<Deposit> {
-        read_deposit(&env, &depositor, &withdrawer, &token)
+        amount: i128,
+        lock_ledgers: u32,
+        clawback_delay_ledgers: Option<u32>,
+    ) -> Result<u64, TimelockError> {
+        // Make sure `depositor` address authorized the deposit call with all the arguments.
+        depositor.require_auth();
+        if amount <= 0 {
+            return Err(TimelockError::InvalidAmount);
+        }
+        let lock_duration = lock_ledgers as u64 * LEDGER_CLOSE_SECONDS;
+        let clawback_delay = check_new_deposit(
+            &env,
+            unlock_after(&env, lock_duration)?,
+            clawback_delay_ledgers.map(|ledgers| ledgers as u64 * LEDGER_CLOSE_SECONDS),
+        )?;
+        // Transfer token from `depositor` to this contract address.
+        token::Client::new(&env, &token).transfer(
+            &depositor,
+            &env.current_contract_address(),
+            &amount,
+        );
+        Ok(create_deposit(
+            &env,
+            depositor,
+            withdrawer,
+            token,
+            amount,
+            VestingSchedule::Cliff,
+            UnlockClock::LedgerSequence,
+            env.ledger().sequence() as u64 + lock_ledgers as u64,
+            clawback_delay / LEDGER_CLOSE_SECONDS,
+            Vec::new(&env),
+        ))
     }
 
-    pub fn withdraw(env: Env, depositor: Address, withdrawer: Address, token: Address) {
+    /// Creates one cliff deposit per `(withdrawer, amount)` entry, pulling the total from
+    /// `depositor` in a single transfer. Either all deposits are created or none is.
+    pub fn batch_deposit(
+        env: Env,
+        depositor: Address,
+        token: Address,
+        deposits: Vec<(Address, i128)>,
+        lock_duration: Option<u64>,
+        clawback_delay: Option<u64>,
+    ) -> Result<Vec<u64>, TimelockError> {
+        // Make sure `depositor` address authorized the deposit call with all the arguments.
+        depositor.require_auth();
+        if deposits.len() > MAX_BATCH_SIZE {
+            return Err(TimelockError::BatchTooLarge);
+        }
+        let mut total: i128 = 0;
+        for (_, amount) in deposits.iter() {
+            // A non-positive entry would let other entries claim more than was transferred.
+            if amount <= 0 {
+                return Err(TimelockError::InvalidAmount);
+            }
+            total = total
+                .checked_add(amount)
+                .ok_or(TimelockError::InvalidAmount)?;
+        }
+        // Only reachable for an empty batch.
+        if total == 0 {
+            return Err(TimelockError::InvalidAmount);
+        }
+        let lock_duration = lock_duration.unwrap_or_else(|| read_lock_duration(&env));
+        let unlock_timestamp = unlock_after(&env, lock_duration)?;
+        let clawback_delay = check_new_deposit(&env, unlock_timestamp, clawback_delay)?;
+        // Transfer the total for all deposits from `depositor` to this contract address.
+        token::Client::new(&env, &token).transfer(
+            &depositor,
+            &env.current_contract_address(),
+            &total,
+        );
+        let mut ids = Vec::new(&env);
+        for (withdrawer, amount) in deposits.iter() {
+            ids.push_back(create_deposit(
+                &env,
+                depositor.clone(),
+                withdrawer,
+                token.clone(),
+                amount,
+                VestingSchedule::Cliff,
+                UnlockClock::Timestamp,
+                unlock_timestamp,
+                clawback_delay,
+                Vec::new(&env),
+            ));
+        }
+        Events::new(&env).batch_deposit(depositor, token, ids.len(), total);
+        Ok(ids)
+    }
+
+    /// Locks `amount` as a single cliff deposit split between several beneficiaries.
+    /// Each `(beneficiary, bps)` entry gets `bps` basis points of the amount, and the
+    /// entries must add up to `BPS_DENOMINATOR`. After unlock, every beneficiary withdraws
+    /// their own share with `withdraw_share`.
+    pub fn deposit_split(
+        env: Env,
+        depositor: Address,
+        token: Address,
+        amount: i128,
+        shares: Vec<(Address, u32)>,
+        lock_duration: Option<u64>,
+        clawback_delay: Option<u64>,
+    ) -> Result<u64, TimelockError> {
+        // Make sure `depositor` address authorized the deposit call with all the arguments.
+        depositor.require_auth();
+        if amount <= 0 {
+            return Err(TimelockError::InvalidAmount);
+        }
+        let shares = check_shares(&env, shares)?;
+        let lock_duration = lock_duration.unwrap_or_else(|| read_lock_duration(&env));
+        let unlock_timestamp = unlock_after(&env, lock_duration)?;
+        let clawback_delay = check_new_deposit(&env, unlock_timestamp, clawback_delay)?;
+        // Transfer token from `depositor` to this contract address.
+        token::Client::new(&env, &token).transfer(
+            &depositor,
+            &env.current_contract_address(),
+            &amount,
+        );
+        Ok(create_deposit(
+            &env,
+            depositor,
+            env.current_contract_address(),
+            token,
+            amount,
+            VestingSchedule::Cliff,
+            UnlockClock::Timestamp,
+            unlock_timestamp,
+            clawback_delay,
+            shares,
+        ))
+    }
+
+    /// Same as `deposit`, but the funds vest according to `schedule` instead of
+    /// unlocking all at once after the lock duration. The time until the schedule
+    /// has fully vested counts as the lock duration of the deposit.
+    pub fn deposit_with_schedule(
+        env: Env,
+        depositor: Address,
+        withdrawer: Address,
+        token: Address,
+        amount: i128,
+        schedule: VestingSchedule,
+        clawback_delay: Option<u64>,
+    ) -> Result<u64, TimelockError> {
+        // Make sure `depositor` address authorized the deposit call with all the arguments.
+        depositor.require_auth();
+        if amount <= 0 {
+            return Err(TimelockError::InvalidAmount);
+        }
+        if !schedule.is_valid(amount) {
+            return Err(TimelockError::InvalidSchedule);
+        }
+        // A cliff has no end of its own; plain `deposit` covers it.
+        let unlock_timestamp = schedule
+            .end_timestamp()
+            .ok_or(TimelockError::InvalidSchedule)?;
+        let clawback_delay = check_new_deposit(&env, unlock_timestamp, clawback_delay)?;
+        // Transfer token from `depositor` to this contract address.
+        token::Client::new(&env, &token).transfer(
+            &depositor,
+            &env.current_contract_address(),
+            &amount,
+        );
+        Ok(create_deposit(
+            &env,
+            depositor,
+            withdrawer,
+            token,
+            amount,
+            schedule,
+            UnlockClock::Timestamp,
+            unlock_timestamp,
+            clawback_delay,
+            Vec::new(&env),
+        ))
+    }
+
+    pub fn get_deposit(env: Env, deposit_id: u64) -> Option<Deposit> {
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        read_deposit(&env, deposit_id)
+    }
+
+    /// Extends the TTL of a deposit and its index entries so they stay live until the
+    /// deposit can be clawed back, within the network's maximum entry TTL. Anyone may
+    /// call this to keep a long lock from being archived.
+    pub fn extend_deposit_ttl(env: Env, deposit_id: u64) -> Result<(), TimelockError> {
+        let deposit = read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        extend_deposit_ttl(&env, &deposit);
+        extend_index_ttls(&env, &deposit);
+        Ok(())
+    }
+
+    /// Deposits funded by `depositor` that are still open, oldest first. At most
+    /// `MAX_PAGE_SIZE` deposits are returned per call.
+    pub fn list_deposits_by_depositor(
+        env: Env,
+        depositor: Address,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<Deposit> {
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        let ids = read_depositor_deposits(&env, &depositor);
+        read_deposit_page(&env, &ids, offset, limit)
+    }
+
+    /// Deposits `withdrawer` can claim that are still open, oldest first. At most
+    /// `MAX_PAGE_SIZE` deposits are returned per call.
+    pub fn list_deposits_by_withdrawer(
+        env: Env,
+        withdrawer: Address,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<Deposit> {
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        let ids = read_withdrawer_deposits(&env, &withdrawer);
+        read_deposit_page(&env, &ids, offset, limit)
+    }
+
+    pub fn get_legacy_deposit(
+        env: Env,
+        depositor: Address,
+        withdrawer: Address,
+        token: Address,
+    ) -> Option<LegacyDeposit> {
+        read_legacy_deposit(&env, &depositor, &withdrawer, &token)
+    }
+
+    /// Moves a deposit stored under the pre-ID `(depositor, withdrawer, token)` key to
+    /// a fresh deposit ID, so it can be withdrawn or clawed back like any other deposit.
+    /// Unlock timing and amount are carried over unchanged, so no authorization is needed.
+    pub fn migrate_legacy_deposit(
+        env: Env,
+        depositor: Address,
+        withdrawer: Address,
+        token: Address,
+    ) -> Result<u64, TimelockError> {
+        let legacy = read_legacy_deposit(&env, &depositor, &withdrawer, &token)
+            .ok_or(TimelockError::NonexistentDeposit)?;
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        let deposit = legacy.clone().into_deposit(
+            &env,
+            next_deposit_id(&env),
+            read_lock_duration(&env),
+            read_clawback_delay(&env),
+        );
+        write_deposit(&env, &deposit);
+        add_deposit_to_indexes(&env, &deposit);
+        remove_legacy_deposit(&env, &legacy);
+        Events::new(&env).migration(deposit.clone());
+        Ok(deposit.id)
+    }
+
+    /// Adds `amount` to an existing cliff deposit. With `TopUpPolicy::ExtendUnlock` the
+    /// unlock time is pushed out to a full lock duration from now if that is later.
+    pub fn top_up(
+        env: Env,
+        deposit_id: u64,
+        amount: i128,
+        policy: TopUpPolicy,
+    ) -> Result<(), TimelockError> {
+        let mut deposit =
+            read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        // Topping up would change the shares beneficiaries may have already claimed.
+        if deposit.is_split() {
+            return Err(TimelockError::SplitDeposit);
+        }
+        // Make sure the depositor has authorized the top-up of this deposit.
+        deposit.depositor.require_auth();
+        if is_paused(&env) {
+            return Err(TimelockError::Paused);
+        }
+        if amount <= 0 {
+            return Err(TimelockError::InvalidAmount);
+        }
+        // Topping up a vesting schedule would change amounts that already vested.
+        if deposit.schedule != VestingSchedule::Cliff {
+            return Err(TimelockError::InvalidSchedule);
+        }
+        let new_amount = deposit
+            .amount
+            .checked_add(amount)
+            .ok_or(TimelockError::InvalidAmount)?;
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        token::Client::new(&env, &deposit.token).transfer(
+            &deposit.depositor,
+            &env.current_contract_address(),
+            &amount,
+        );
+        deposit.amount = new_amount;
+        if policy == TopUpPolicy::ExtendUnlock {
+            let extended_unlock = deposit.now(&env) + deposit.lock_duration;
+            deposit.unlock_timestamp = deposit.unlock_timestamp.max(extended_unlock);
+        }
+        write_deposit(&env, &deposit);
+        extend_index_ttls(&env, &deposit);
+        Events::new(&env).top_up(deposit, amount);
+        Ok(())
+    }
+
+    /// Transfers everything that has vested and was not withdrawn yet to the withdrawer.
+    pub fn withdraw(env: Env, deposit_id: u64) -> Result<(), TimelockError> {
+        let deposit = read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        if deposit.is_split() {
+            return Err(TimelockError::SplitDeposit);
+        }
         // Make sure withdrawer has authorized this call, which ensures their identity.
-        withdrawer.require_auth();
-        let deposit_opt = read_deposit(&env, &depositor, &withdrawer, &token);
-        if deposit_opt.is_none() {
-            panic!(
-                "TimelockNonexistentDeposit({:?}, {:?}, {:?})",
-                depositor, withdrawer, token
-            );
+        deposit.withdrawer.require_auth();
+        let claimable = deposit.vested_amount(deposit.now(&env)) - deposit.withdrawn;
+        if claimable <= 0 {
+            return Err(TimelockError::LockedDeposit);
         }
-        let deposit = deposit_opt.unwrap();
-        if env.ledger().timestamp() < deposit.unlock_timestamp {
-            Events::new(&env).withdrawal(deposit, false);
-            panic!(
-                "TimelockLockedDeposit({:?}, {:?}, {:?})",
-                depositor, withdrawer, token
-            );
+        let deposit = pay_out(&env, deposit, claimable);
+        Events::new(&env).withdrawal(deposit, claimable);
+        Ok(())
+    }
+
+    /// Transfers `amount` out of the vested and not yet withdrawn funds to the withdrawer.
+    /// The deposit entry is only removed once nothing is left in it.
+    pub fn withdraw_partial(env: Env, deposit_id: u64, amount: i128) -> Result<(), TimelockError> {
+        let deposit = read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        if deposit.is_split() {
+            return Err(TimelockError::SplitDeposit);
+        }
+        // Make sure withdrawer has authorized this call, which ensures their identity.
+        deposit.withdrawer.require_auth();
+        let claimable = deposit.vested_amount(deposit.now(&env)) - deposit.withdrawn;
+        if claimable <= 0 {
+            return Err(TimelockError::LockedDeposit);
+        }
+        if amount <= 0 || amount > claimable {
+            return Err(TimelockError::InvalidAmount);
+        }
+        let deposit = pay_out(&env, deposit, amount);
+        Events::new(&env).partial_withdrawal(deposit, amount);
+        Ok(())
+    }
+
+    /// Transfers `beneficiary`'s share of a split deposit to them once it has unlocked.
+    /// Each share can be withdrawn once; the deposit entry is removed after the last one.
+    pub fn withdraw_share(
+        env: Env,
+        deposit_id: u64,
+        beneficiary: Address,
+    ) -> Result<(), TimelockError> {
+        let mut deposit =
+            read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        // Make sure the beneficiary has authorized this call, which ensures their identity.
+        beneficiary.require_auth();
+        let index = deposit
+            .shares
+            .iter()
+            .position(|share| share.beneficiary == beneficiary && !share.claimed)
+            .ok_or(TimelockError::NoShare)? as u32;
+        if deposit.now(&env) < deposit.unlock_timestamp {
+            return Err(TimelockError::LockedDeposit);
         }
         env.storage()
             .instance()
             .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
-        // Transfer the stored amount of token to withdrawer after passing all the checks.
-        token::Client::new(&env, &token).transfer(
+        let amount = deposit.share_amount(index);
+        token::Client::new(&env, &deposit.token).transfer(
             &env.current_contract_address(),
-            &withdrawer,
-            &deposit.amount,
+            &beneficiary,
+            &amount,
         );
-        // Remove the deposit entry to prevent any further withdrawals.
-        remove_deposit(&env, &deposit);
-        Events::new(&env).withdrawal(deposit, true);
+        let mut share = deposit.shares.get_unchecked(index);
+        share.claimed = true;
+        deposit.shares.set(index, share);
+        deposit.withdrawn += amount;
+        if deposit.withdrawn == deposit.amount {
+            remove_deposit(&env, &deposit);
+        } else {
+            write_deposit(&env, &deposit);
+            remove_beneficiary_index(&env, deposit.id, &beneficiary);
+        }
+        Events::new(&env).share_withdrawal(deposit, beneficiary, amount);
+        Ok(())
     }
 
-    pub fn clawback(env: Env, depositor: Address, withdrawer: Address, token: Address) {
-        // Make sure `depositor` address authorized the clawback call with all the arguments.
-        depositor.require_auth();
-        let deposit_opt = read_deposit(&env, &depositor, &withdrawer, &token);
-        if deposit_opt.is_none() {
-            panic!(
-                "TimelockNonexistentDeposit({:?}, {:?}, {:?})",
-                depositor, withdrawer, token
-            );
+    /// Controls whether reassigning the claim on a deposit needs the depositor's approval.
+    /// Requiring approval takes away the withdrawer's right to assign the claim on their
+    /// own, so turning it on needs the withdrawer's authorization as well.
+    pub fn set_claim_transfer_approval(
+        env: Env,
+        deposit_id: u64,
+        required: bool,
+    ) -> Result<(), TimelockError> {
+        let mut deposit =
+            read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        deposit.depositor.require_auth();
+        if required && !deposit.claim_needs_approval {
+            deposit.withdrawer.require_auth();
         }
-        let deposit = deposit_opt.unwrap();
-        if env.ledger().timestamp() < deposit.unlock_timestamp + read_clawback_delay(&env) {
-            Events::new(&env).clawback(deposit, false);
-            panic!(
-                "TimelockLockedClawback({:?}, {:?}, {:?})",
-                depositor, withdrawer, token
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        deposit.claim_needs_approval = required;
+        write_deposit(&env, &deposit);
+        Events::new(&env).claim_transfer_approval(deposit);
+        Ok(())
+    }
+
+    /// Reassigns the right to withdraw a deposit to `new_withdrawer`. Amount, vesting,
+    /// unlock and clawback timing stay as they are.
+    pub fn transfer_claim(
+        env: Env,
+        deposit_id: u64,
+        new_withdrawer: Address,
+    ) -> Result<(), TimelockError> {
+        let mut deposit =
+            read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        if deposit.is_split() {
+            return Err(TimelockError::SplitDeposit);
+        }
+        // Make sure the current withdrawer has authorized handing over the claim.
+        deposit.withdrawer.require_auth();
+        if deposit.claim_needs_approval {
+            deposit.depositor.require_auth();
+        }
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        let previous_withdrawer = deposit.withdrawer.clone();
+        deposit.withdrawer = new_withdrawer;
+        write_deposit(&env, &deposit);
+        move_withdrawer_index(&env, &deposit, &previous_withdrawer);
+        Events::new(&env).claim_transfer(deposit, previous_withdrawer);
+        Ok(())
+    }
+
+    /// Lets the depositor pay out everything left in a deposit to the withdrawer right
+    /// away, regardless of the unlock time.
+    pub fn release_early(env: Env, deposit_id: u64) -> Result<(), TimelockError> {
+        let deposit = read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        // Make sure the depositor has authorized the early release of this deposit.
+        deposit.depositor.require_auth();
+        let remaining = deposit.amount - deposit.withdrawn;
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        let token_client = token::Client::new(&env, &deposit.token);
+        if deposit.is_split() {
+            // Pay every share that was not withdrawn yet to its beneficiary.
+            for (index, share) in deposit.shares.iter().enumerate() {
+                if !share.claimed {
+                    token_client.transfer(
+                        &env.current_contract_address(),
+                        &share.beneficiary,
+                        &deposit.share_amount(index as u32),
+                    );
+                }
+            }
+        } else {
+            token_client.transfer(
+                &env.current_contract_address(),
+                &deposit.withdrawer,
+                &remaining,
             );
         }
+        remove_deposit(&env, &deposit);
+        Events::new(&env).early_release(deposit, remaining);
+        Ok(())
+    }
+
+    /// Lets the withdrawer give up their claim, returning everything left in a deposit
+    /// to the depositor right away.
+    pub fn return_to_depositor(env: Env, deposit_id: u64) -> Result<(), TimelockError> {
+        let deposit = read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        if deposit.is_split() {
+            return Err(TimelockError::SplitDeposit);
+        }
+        // Make sure the withdrawer has authorized giving up this deposit.
+        deposit.withdrawer.require_auth();
+        let remaining = deposit.amount - deposit.withdrawn;
         env.storage()
             .instance()
             .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
-        // Transfer back the stored amount of token to depositor after passing all the checks.
-        token::Client::new(&env, &token).transfer(
+        token::Client::new(&env, &deposit.token).transfer(
             &env.current_contract_address(),
-            &depositor,
-            &deposit.amount,
+            &deposit.depositor,
+            &remaining,
         );
-        // Remove the deposit entry to prevent any further clawbacks.
         remove_deposit(&env, &deposit);
-        Events::new(&env).clawback(deposit, true);
+        Events::new(&env).return_to_depositor(deposit, remaining);
+        Ok(())
+    }
+
+    /// Returns unvested funds to the depositor. Once `clawback_delay` has passed since
+    /// vesting started, the part that has not vested yet can be reclaimed, while the
+    /// vested part stays withdrawable. Once `clawback_delay` has passed since the whole
+    /// amount vested, everything that was not withdrawn can be reclaimed.
+    pub fn clawback(env: Env, deposit_id: u64) -> Result<(), TimelockError> {
+        let mut deposit =
+            read_deposit(&env, deposit_id).ok_or(TimelockError::NonexistentDeposit)?;
+        // Make sure the depositor has authorized the clawback of this deposit.
+        deposit.depositor.require_auth();
+        let now = deposit.now(&env);
+        let clawback_delay = deposit.clawback_delay;
+        let vesting_start = deposit
+            .schedule
+            .start_timestamp()
+            .unwrap_or(deposit.unlock_timestamp);
+        let reclaimable = if now >= deposit.unlock_timestamp + clawback_delay {
+            deposit.amount - deposit.withdrawn
+        } else if now >= vesting_start + clawback_delay {
+            deposit.amount - deposit.vested_amount(now)
+        } else {
+            0
+        };
+        if reclaimable <= 0 {
+            return Err(TimelockError::LockedClawback);
+        }
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+        // Transfer back the reclaimable amount of token to depositor after passing all the checks.
+        token::Client::new(&env, &deposit.token).transfer(
+            &env.current_contract_address(),
+            &deposit.depositor,
+            &reclaimable,
+        );
+        deposit.amount -= reclaimable;
+        if deposit.withdrawn == deposit.amount {
+            // Remove the deposit entry to prevent any further clawbacks.
+            remove_deposit(&env, &deposit);
+        } else {
+            // Whatever vested so far stays with the withdrawer and is claimable right away.
+            deposit.schedule = VestingSchedule::Cliff;
+            deposit.unlock_timestamp = now;
+            write_deposit(&env, &deposit);
+        }
+        Events::new(&env).clawback(deposit, reclaimable);
+        Ok(())
+    }
+}
+
+fn read_deposit_page(env: &Env, ids: &Vec<u64>, offset: u32, limit: u32) -> Vec<Deposit> {
+    let end = offset
+        .saturating_add(limit.min(MAX_PAGE_SIZE))
+        .min(ids.len());
+    let mut deposits = Vec::new(env);
+    for i in offset..end {
+        if let Some(deposit) = read_deposit(env, ids.get_unchecked(i)) {
+            deposits.push_back(deposit);
+        }
+    }
+    deposits
+}
+
+fn set_paused(env: &Env, paused: bool) -> Result<(), TimelockError> {
+    let admin = read_admin(env);
+    admin.require_auth();
+    env.storage()
+        .instance()
+        .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    write_paused(env, paused);
+    Events::new(env).paused(admin, paused);
+    Ok(())
+}
+
+/// Transfers `amount` to the withdrawer and records it as withdrawn, removing the
+/// deposit entry once everything has been paid out.
+fn pay_out(env: &Env, mut deposit: Deposit, amount: i128) -> Deposit {
+    env.storage()
+        .instance()
+        .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    token::Client::new(env, &deposit.token).transfer(
+        &env.current_contract_address(),
+        &deposit.withdrawer,
+        &amount,
+    );
+    deposit.withdrawn += amount;
+    if deposit.withdrawn == deposit.amount {
+        // Remove the deposit entry to prevent any further withdrawals.
+        remove_deposit(env, &deposit);
+    } else {
+        write_deposit(env, &deposit);
+    }
+    deposit
+}
+
+/// Timestamp `lock_duration` seconds from now. A duration that overflows the timestamp is
+/// out of any bounds and rejected as such.
+fn unlock_after(env: &Env, lock_duration: u64) -> Result<u64, TimelockError> {
+    env.ledger()
+        .timestamp()
+        .checked_add(lock_duration)
+        .ok_or(TimelockError::InvalidDuration)
+}
+
+/// Checks that a new deposit unlocking at `unlock_timestamp` may be created, and returns
+/// the clawback delay it will use.
+fn check_new_deposit(
+    env: &Env,
+    unlock_timestamp: u64,
+    clawback_delay: Option<u64>,
+) -> Result<u64, TimelockError> {
+    if is_paused(env) {
+        return Err(TimelockError::Paused);
+    }
+    let lock_duration = unlock_timestamp.saturating_sub(env.ledger().timestamp());
+    let clawback_delay = clawback_delay.unwrap_or_else(|| read_clawback_delay(env));
+    if !read_lock_duration_bounds(env).contains(lock_duration)
+        || !read_clawback_delay_bounds(env).contains(clawback_delay)
+    {
+        return Err(TimelockError::InvalidDuration);
+    }
+    Ok(clawback_delay)
+}
+
+/// Records a new deposit that passed `check_new_deposit`. The caller is responsible for
+/// transferring `amount` of `token` from the depositor to this contract.
+#[allow(clippy::too_many_arguments)]
+fn create_deposit(
+    env: &Env,
+    depositor: Address,
+    withdrawer: Address,
+    token: Address,
+    amount: i128,
+    schedule: VestingSchedule,
+    clock: UnlockClock,
+    unlock_timestamp: u64,
+    clawback_delay: u64,
+    shares: Vec<Share>,
+) -> u64 {
+    env.storage()
+        .instance()
+        .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    // Store all the necessary info to allow the withdrawer to claim it.
+    let deposit = Deposit {
+        id: next_deposit_id(env),
+        depositor,
+        withdrawer,
+        token,
+        amount,
+        unlock_timestamp,
+        schedule,
+        clock,
+        withdrawn: 0,
+        lock_duration: unlock_timestamp.saturating_sub(clock.now(env)),
+        clawback_delay,
+        claim_needs_approval: false,
+        shares,
+    };
+    write_deposit(env, &deposit);
+    add_deposit_to_indexes(env, &deposit);
+    if deposit.is_split() {
+        Events::new(env).split_deposit(deposit.clone());
+    } else {
+        Events::new(env).deposit(deposit.clone());
+    }
+    deposit.id
+}
+
+/// Turns the `(beneficiary, bps)` entries of a split deposit into unclaimed shares.
+/// Every beneficiary must appear once with a positive share, and the shares must add
+/// up to the whole deposit.
+fn check_shares(env: &Env, entries: Vec<(Address, u32)>) -> Result<Vec<Share>, TimelockError> {
+    if entries.len() > MAX_SHARES {
+        return Err(TimelockError::InvalidShares);
+    }
+    let mut shares: Vec<Share> = Vec::new(env);
+    let mut total: u32 = 0;
+    for (beneficiary, bps) in entries.iter() {
+        if bps == 0 || shares.iter().any(|share| share.beneficiary == beneficiary) {
+            return Err(TimelockError::InvalidShares);
+        }
+        total = total.saturating_add(bps);
+        shares.push_back(Share {
+            beneficiary,
+            bps,
+            claimed: false,
+        });
+    }
+    if total != BPS_DENOMINATOR {
+        return Err(TimelockError::InvalidShares);
     }
+    Ok(shares)
 }
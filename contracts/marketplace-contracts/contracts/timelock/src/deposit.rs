@@ -1,23 +1,177 @@
-use crate::storage_types::{DataKey, DEPOSIT_BUMP_AMOUNT, DEPOSIT_LIFETIME_THRESHOLD};
-use soroban_sdk::{contracttype, Address, Env};
+use crate::deposit_index::remove_deposit_from_indexes;
+use crate::storage_types::{
+    DataKey, DAY_IN_LEDGERS, DEPOSIT_BUMP_AMOUNT, DEPOSIT_LIFETIME_THRESHOLD, LEDGER_CLOSE_SECONDS,
+};
+use crate::vesting::VestingSchedule;
+use soroban_sdk::{contracttype, vec, Address, Env, Vec};
+
+/// Basis points making up the whole amount of a split deposit.
+pub const BPS_DENOMINATOR: u32 = 10_000;
 
 #[derive(Clone, PartialEq, Debug)]
 #[contracttype]
 pub struct Deposit {
+    pub id: u64,
     pub depositor: Address,
     pub withdrawer: Address,
     pub token: Address,
     pub amount: i128,
+    /// Timestamp at which the whole amount has vested.
     pub unlock_timestamp: u64,
+    pub schedule: VestingSchedule,
+    /// Clock the unlock and clawback timing of the deposit is measured on.
+    pub clock: UnlockClock,
+    pub withdrawn: i128,
+    pub lock_duration: u64,
+    pub clawback_delay: u64,
+    /// Whether `transfer_claim` also needs the depositor's authorization.
+    pub claim_needs_approval: bool,
+    /// Beneficiaries of a split deposit, each withdrawing their own share. Empty for
+    /// deposits paid out to `withdrawer` alone. In a split deposit `withdrawer` is the
+    /// contract itself, which holds the funds on behalf of the beneficiaries.
+    pub shares: Vec<Share>,
 }
 
-pub fn read_deposit(
-    env: &Env,
-    depositor: &Address,
-    withdrawer: &Address,
-    token: &Address,
-) -> Option<Deposit> {
-    let key = DataKey::Deposit(depositor.clone(), withdrawer.clone(), token.clone());
+/// With `UnlockClock::LedgerSequence`, a deposit's `unlock_timestamp` is a ledger
+/// sequence number, and its `lock_duration` and `clawback_delay` are counted in ledgers.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[contracttype]
+pub enum UnlockClock {
+    Timestamp,
+    LedgerSequence,
+}
+
+impl UnlockClock {
+    pub fn now(&self, env: &Env) -> u64 {
+        match self {
+            UnlockClock::Timestamp => env.ledger().timestamp(),
+            UnlockClock::LedgerSequence => env.ledger().sequence() as u64,
+        }
+    }
+}
+
+/// A beneficiary's part of a split deposit, in basis points of the deposit amount.
+#[derive(Clone, PartialEq, Debug)]
+#[contracttype]
+pub struct Share {
+    pub beneficiary: Address,
+    pub bps: u32,
+    pub claimed: bool,
+}
+
+impl Deposit {
+    pub fn vested_amount(&self, now: u64) -> i128 {
+        self.schedule
+            .vested_amount(self.amount, self.unlock_timestamp, now)
+    }
+
+    /// Current time on the deposit's clock.
+    pub fn now(&self, env: &Env) -> u64 {
+        self.clock.now(env)
+    }
+
+    pub fn is_split(&self) -> bool {
+        !self.shares.is_empty()
+    }
+
+    /// Addresses that can withdraw from the deposit.
+    pub fn beneficiaries(&self, env: &Env) -> Vec<Address> {
+        if self.is_split() {
+            let mut beneficiaries = Vec::new(env);
+            for share in self.shares.iter() {
+                beneficiaries.push_back(share.beneficiary);
+            }
+            beneficiaries
+        } else {
+            vec![env, self.withdrawer.clone()]
+        }
+    }
+
+    /// Amount paid out for the share at `index`. Each share is rounded down, and the
+    /// last share receives what rounding leaves over, so the shares add up to `amount`.
+    pub fn share_amount(&self, index: u32) -> i128 {
+        if index + 1 < self.shares.len() {
+            let bps = self.shares.get_unchecked(index).bps;
+            self.amount * bps as i128 / BPS_DENOMINATOR as i128
+        } else {
+            let others: i128 = (0..index).map(|i| self.share_amount(i)).sum();
+            self.amount - others
+        }
+    }
+
+    /// Number of ledgers the deposit's storage entries have to live for, so they stay
+    /// live until the depositor can claw back, plus `DEPOSIT_BUMP_AMOUNT` to act on it.
+    /// Capped at the network's maximum entry TTL.
+    pub fn ttl(&self, env: &Env) -> u32 {
+        let horizon = self
+            .unlock_timestamp
+            .saturating_add(self.clawback_delay)
+            .saturating_sub(self.now(env));
+        let horizon_in_ledgers = match self.clock {
+            UnlockClock::Timestamp => horizon.div_ceil(LEDGER_CLOSE_SECONDS),
+            UnlockClock::LedgerSequence => horizon,
+        };
+        let ledgers = horizon_in_ledgers.saturating_add(DEPOSIT_BUMP_AMOUNT as u64);
+        ledgers.min(env.storage().max_ttl() as u64) as u32
+    }
+}
+
+/// Whether topping up a deposit keeps its unlock time or pushes it out by the
+/// deposit's lock duration, counted from the time of the top-up.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[contracttype]
+pub enum TopUpPolicy {
+    KeepUnlock,
+    ExtendUnlock,
+}
+
+/// Deposit layout used before deposits were addressed by ID. Entries with this
+/// layout are still stored under `DataKey::Deposit(depositor, withdrawer, token)`.
+#[derive(Clone, PartialEq, Debug)]
+#[contracttype]
+pub struct LegacyDeposit {
+    pub depositor: Address,
+    pub withdrawer: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub unlock_timestamp: u64,
+}
+
+impl LegacyDeposit {
+    pub fn into_deposit(
+        self,
+        env: &Env,
+        id: u64,
+        lock_duration: u64,
+        clawback_delay: u64,
+    ) -> Deposit {
+        Deposit {
+            id,
+            depositor: self.depositor,
+            withdrawer: self.withdrawer,
+            token: self.token,
+            amount: self.amount,
+            unlock_timestamp: self.unlock_timestamp,
+            schedule: VestingSchedule::Cliff,
+            clock: UnlockClock::Timestamp,
+            withdrawn: 0,
+            lock_duration,
+            clawback_delay,
+            claim_needs_approval: false,
+            shares: Vec::new(env),
+        }
+    }
+}
+
+pub fn next_deposit_id(env: &Env) -> u64 {
+    let key = DataKey::NextDepositId;
+    let id: u64 = env.storage().instance().get(&key).unwrap_or(0);
+    env.storage().instance().set(&key, &(id + 1));
+    id
+}
+
+pub fn read_deposit(env: &Env, id: u64) -> Option<Deposit> {
+    let key = DataKey::DepositById(id);
     if let Some(deposit) = env.storage().persistent().get::<DataKey, Deposit>(&key) {
         env.storage().persistent().extend_ttl(
             &key,
@@ -31,21 +185,52 @@ pub fn read_deposit(
 }
 
 pub fn write_deposit(env: &Env, deposit: &Deposit) {
-    let Deposit {
-        depositor,
-        withdrawer,
-        token,
-        ..
-    } = deposit.clone();
-    let key = DataKey::Deposit(depositor, withdrawer, token);
+    let key = DataKey::DepositById(deposit.id);
     env.storage().persistent().set(&key, deposit);
-    env.storage()
-        .persistent()
-        .extend_ttl(&key, DEPOSIT_LIFETIME_THRESHOLD, DEPOSIT_BUMP_AMOUNT);
+    extend_deposit_ttl(env, deposit);
+}
+
+/// Extends the TTL of the deposit entry to cover its unlock and clawback horizon.
+pub fn extend_deposit_ttl(env: &Env, deposit: &Deposit) {
+    let ttl = deposit.ttl(env);
+    env.storage().persistent().extend_ttl(
+        &DataKey::DepositById(deposit.id),
+        ttl.saturating_sub(DAY_IN_LEDGERS),
+        ttl,
+    );
 }
 
 pub fn remove_deposit(env: &Env, deposit: &Deposit) {
-    let Deposit {
+    let key = DataKey::DepositById(deposit.id);
+    env.storage().persistent().remove(&key);
+    remove_deposit_from_indexes(env, deposit);
+}
+
+pub fn read_legacy_deposit(
+    env: &Env,
+    depositor: &Address,
+    withdrawer: &Address,
+    token: &Address,
+) -> Option<LegacyDeposit> {
+    let key = DataKey::Deposit(depositor.clone(), withdrawer.clone(), token.clone());
+    if let Some(deposit) = env
+        .storage()
+        .persistent()
+        .get::<DataKey, LegacyDeposit>(&key)
+    {
+        env.storage().persistent().extend_ttl(
+            &key,
+            DEPOSIT_LIFETIME_THRESHOLD,
+            DEPOSIT_BUMP_AMOUNT,
+        );
+        Some(deposit)
+    } else {
+        None
+    }
+}
+
+pub fn remove_legacy_deposit(env: &Env, deposit: &LegacyDeposit) {
+    let LegacyDeposit {
         depositor,
         withdrawer,
         token,
@@ -0,0 +1,126 @@
+use crate::deposit::Deposit;
+use crate::storage_types::{
+    DataKey, DAY_IN_LEDGERS, DEPOSIT_BUMP_AMOUNT, DEPOSIT_LIFETIME_THRESHOLD,
+};
+use soroban_sdk::{Address, Env, Vec};
+
+fn read_index(env: &Env, key: &DataKey) -> Vec<u64> {
+    if let Some(ids) = env.storage().persistent().get::<DataKey, Vec<u64>>(key) {
+        env.storage()
+            .persistent()
+            .extend_ttl(key, DEPOSIT_LIFETIME_THRESHOLD, DEPOSIT_BUMP_AMOUNT);
+        ids
+    } else {
+        Vec::new(env)
+    }
+}
+
+fn write_index(env: &Env, key: &DataKey, ids: &Vec<u64>) {
+    if ids.is_empty() {
+        env.storage().persistent().remove(key);
+    } else {
+        env.storage().persistent().set(key, ids);
+        env.storage()
+            .persistent()
+            .extend_ttl(key, DEPOSIT_LIFETIME_THRESHOLD, DEPOSIT_BUMP_AMOUNT);
+    }
+}
+
+/// Keeps an index entry alive for at least `ttl` ledgers. Entries are shared between
+/// deposits, so the TTL only ever grows to the longest horizon among them.
+fn extend_index_ttl(env: &Env, key: &DataKey, ttl: u32) {
+    if env.storage().persistent().has(key) {
+        env.storage()
+            .persistent()
+            .extend_ttl(key, ttl.saturating_sub(DAY_IN_LEDGERS), ttl);
+    }
+}
+
+fn add_to_index(env: &Env, key: DataKey, id: u64, ttl: u32) {
+    let mut ids = read_index(env, &key);
+    ids.push_back(id);
+    write_index(env, &key, &ids);
+    extend_index_ttl(env, &key, ttl);
+}
+
+fn remove_from_index(env: &Env, key: DataKey, id: u64) {
+    let mut ids = read_index(env, &key);
+    if let Some(index) = ids.first_index_of(id) {
+        ids.remove(index);
+        write_index(env, &key, &ids);
+    }
+}
+
+pub fn read_depositor_deposits(env: &Env, depositor: &Address) -> Vec<u64> {
+    read_index(env, &DataKey::DepositorDeposits(depositor.clone()))
+}
+
+pub fn read_withdrawer_deposits(env: &Env, withdrawer: &Address) -> Vec<u64> {
+    read_index(env, &DataKey::WithdrawerDeposits(withdrawer.clone()))
+}
+
+pub fn add_deposit_to_indexes(env: &Env, deposit: &Deposit) {
+    let ttl = deposit.ttl(env);
+    add_to_index(
+        env,
+        DataKey::DepositorDeposits(deposit.depositor.clone()),
+        deposit.id,
+        ttl,
+    );
+    for beneficiary in deposit.beneficiaries(env).iter() {
+        add_to_index(
+            env,
+            DataKey::WithdrawerDeposits(beneficiary),
+            deposit.id,
+            ttl,
+        );
+    }
+}
+
+/// Extends the TTL of the index entries listing `deposit` to cover its horizon.
+pub fn extend_index_ttls(env: &Env, deposit: &Deposit) {
+    let ttl = deposit.ttl(env);
+    extend_index_ttl(
+        env,
+        &DataKey::DepositorDeposits(deposit.depositor.clone()),
+        ttl,
+    );
+    for beneficiary in deposit.beneficiaries(env).iter() {
+        extend_index_ttl(env, &DataKey::WithdrawerDeposits(beneficiary), ttl);
+    }
+}
+
+pub fn remove_deposit_from_indexes(env: &Env, deposit: &Deposit) {
+    remove_from_index(
+        env,
+        DataKey::DepositorDeposits(deposit.depositor.clone()),
+        deposit.id,
+    );
+    for beneficiary in deposit.beneficiaries(env).iter() {
+        remove_from_index(env, DataKey::WithdrawerDeposits(beneficiary), deposit.id);
+    }
+}
+
+/// Drops `deposit` from the listing of a beneficiary who has claimed their share.
+pub fn remove_beneficiary_index(env: &Env, deposit_id: u64, beneficiary: &Address) {
+    remove_from_index(
+        env,
+        DataKey::WithdrawerDeposits(beneficiary.clone()),
+        deposit_id,
+    );
+}
+
+/// Moves `deposit` from `previous_withdrawer`'s index to its current withdrawer's index.
+pub fn move_withdrawer_index(env: &Env, deposit: &Deposit, previous_withdrawer: &Address) {
+    remove_from_index(
+        env,
+        DataKey::WithdrawerDeposits(previous_withdrawer.clone()),
+        deposit.id,
+    );
+    add_to_index(
+        env,
+        DataKey::WithdrawerDeposits(deposit.withdrawer.clone()),
+        deposit.id,
+        deposit.ttl(env),
+    );
+}
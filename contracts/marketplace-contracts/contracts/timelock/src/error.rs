@@ -0,0 +1,21 @@
+use soroban_sdk::contracterror;
+
+/// Errors returned by the Timelock entrypoints. A failed call is rolled back together
+/// with any events it published, so the error code in the transaction result is the
+/// only record of why a withdrawal or clawback was rejected.
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum TimelockError {
+    NonexistentDeposit = 1,
+    LockedDeposit = 2,
+    LockedClawback = 3,
+    InvalidSchedule = 4,
+    InvalidDuration = 5,
+    InvalidAmount = 6,
+    Paused = 7,
+    BatchTooLarge = 8,
+    SplitDeposit = 9,
+    InvalidShares = 10,
+    NoShare = 11,
+}
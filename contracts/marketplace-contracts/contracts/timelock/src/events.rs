@@ -1,5 +1,5 @@
 use crate::deposit::Deposit;
-use soroban_sdk::{symbol_short, Env, Symbol};
+use soroban_sdk::{symbol_short, Address, BytesN, Env, Symbol};
 
 pub struct Events {
     env: Env,
@@ -18,34 +18,167 @@ impl Events {
             deposit.withdrawer,
             deposit.token,
         );
-        self.env
-            .events()
-            .publish(topics, (deposit.amount, deposit.unlock_timestamp));
+        self.env.events().publish(
+            topics,
+            (
+                deposit.id,
+                deposit.amount,
+                deposit.unlock_timestamp,
+                deposit.lock_duration,
+                deposit.clawback_delay,
+            ),
+        );
+    }
+
+    pub fn split_deposit(&self, deposit: Deposit) {
+        let topics = (
+            Symbol::new(&self.env, "split_deposit"),
+            deposit.depositor,
+            deposit.token,
+        );
+        self.env.events().publish(
+            topics,
+            (
+                deposit.id,
+                deposit.amount,
+                deposit.unlock_timestamp,
+                deposit.clawback_delay,
+                deposit.shares,
+            ),
+        );
     }
 
-    pub fn withdrawal(&self, deposit: Deposit, success: bool) {
+    pub fn batch_deposit(&self, depositor: Address, token: Address, count: u32, total: i128) {
+        let topics = (Symbol::new(&self.env, "batch_deposit"), depositor, token);
+        self.env.events().publish(topics, (count, total));
+    }
+
+    pub fn withdrawal(&self, deposit: Deposit, amount: i128) {
         let topics = (
             Symbol::new(&self.env, "withdrawal"),
             deposit.depositor,
             deposit.withdrawer,
             deposit.token,
-            success,
         );
         self.env
             .events()
-            .publish(topics, (deposit.amount, deposit.unlock_timestamp));
+            .publish(topics, (deposit.id, amount, deposit.unlock_timestamp));
+    }
+
+    pub fn share_withdrawal(&self, deposit: Deposit, beneficiary: Address, amount: i128) {
+        let topics = (
+            Symbol::new(&self.env, "share_withdrawal"),
+            deposit.depositor,
+            beneficiary,
+            deposit.token,
+        );
+        self.env.events().publish(topics, (deposit.id, amount));
     }
 
-    pub fn clawback(&self, deposit: Deposit, success: bool) {
+    pub fn clawback(&self, deposit: Deposit, amount: i128) {
         let topics = (
             symbol_short!("clawback"),
             deposit.depositor,
             deposit.withdrawer,
             deposit.token,
-            success,
         );
         self.env
             .events()
-            .publish(topics, (deposit.amount, deposit.unlock_timestamp));
+            .publish(topics, (deposit.id, amount, deposit.unlock_timestamp));
+    }
+
+    pub fn migration(&self, deposit: Deposit) {
+        let topics = (
+            symbol_short!("migrate"),
+            deposit.depositor,
+            deposit.withdrawer,
+            deposit.token,
+        );
+        self.env.events().publish(topics, deposit.id);
+    }
+
+    pub fn early_release(&self, deposit: Deposit, amount: i128) {
+        let topics = (
+            Symbol::new(&self.env, "early_release"),
+            deposit.depositor,
+            deposit.withdrawer,
+            deposit.token,
+        );
+        self.env.events().publish(topics, (deposit.id, amount));
+    }
+
+    pub fn return_to_depositor(&self, deposit: Deposit, amount: i128) {
+        let topics = (
+            Symbol::new(&self.env, "return_to_depositor"),
+            deposit.depositor,
+            deposit.withdrawer,
+            deposit.token,
+        );
+        self.env.events().publish(topics, (deposit.id, amount));
+    }
+
+    pub fn claim_transfer(&self, deposit: Deposit, previous_withdrawer: Address) {
+        let topics = (
+            Symbol::new(&self.env, "claim_transfer"),
+            deposit.depositor,
+            previous_withdrawer,
+            deposit.token,
+        );
+        self.env
+            .events()
+            .publish(topics, (deposit.id, deposit.withdrawer));
+    }
+
+    pub fn claim_transfer_approval(&self, deposit: Deposit) {
+        let topics = (
+            Symbol::new(&self.env, "claim_transfer_approval"),
+            deposit.depositor,
+            deposit.withdrawer,
+            deposit.token,
+        );
+        self.env
+            .events()
+            .publish(topics, (deposit.id, deposit.claim_needs_approval));
+    }
+
+    pub fn partial_withdrawal(&self, deposit: Deposit, amount: i128) {
+        let topics = (
+            Symbol::new(&self.env, "partial_withdrawal"),
+            deposit.depositor,
+            deposit.withdrawer,
+            deposit.token,
+        );
+        self.env.events().publish(
+            topics,
+            (deposit.id, amount, deposit.amount - deposit.withdrawn),
+        );
+    }
+
+    pub fn top_up(&self, deposit: Deposit, amount: i128) {
+        let topics = (
+            symbol_short!("top_up"),
+            deposit.depositor,
+            deposit.withdrawer,
+            deposit.token,
+        );
+        self.env.events().publish(
+            topics,
+            (deposit.id, amount, deposit.amount, deposit.unlock_timestamp),
+        );
+    }
+
+    pub fn set_admin(&self, admin: Address, new_admin: Address) {
+        let topics = (symbol_short!("set_admin"), admin);
+        self.env.events().publish(topics, new_admin);
+    }
+
+    pub fn paused(&self, admin: Address, paused: bool) {
+        let topics = (symbol_short!("paused"), admin);
+        self.env.events().publish(topics, paused);
+    }
+
+    pub fn upgrade(&self, admin: Address, new_wasm_hash: BytesN<32>) {
+        let topics = (symbol_short!("upgrade"), admin);
+        self.env.events().publish(topics, new_wasm_hash);
     }
 }
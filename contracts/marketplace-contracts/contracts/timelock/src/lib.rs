@@ -1,11 +1,16 @@
 #![no_std]
 
+mod admin;
 mod clawback_delay;
 mod contract;
 mod deposit;
+mod deposit_index;
+mod error;
 mod events;
 mod lock_duration;
 mod storage_types;
 mod test;
+mod vesting;
 
 pub use crate::contract::TimelockClient;
+pub use crate::error::TimelockError;
@@ -1,6 +1,6 @@
 use soroban_sdk::Env;
 
-use crate::storage_types::DataKey;
+use crate::storage_types::{DataKey, DurationBounds};
 
 pub fn read_lock_duration(env: &Env) -> u64 {
     let key = DataKey::LockDuration;
@@ -11,3 +11,13 @@ pub fn write_lock_duration(env: &Env, lock_duration: u64) {
     let key = DataKey::LockDuration;
     env.storage().instance().set(&key, &lock_duration);
 }
+
+pub fn read_lock_duration_bounds(env: &Env) -> DurationBounds {
+    let key = DataKey::LockDurationBounds;
+    env.storage().instance().get(&key).unwrap()
+}
+
+pub fn write_lock_duration_bounds(env: &Env, bounds: &DurationBounds) {
+    let key = DataKey::LockDurationBounds;
+    env.storage().instance().set(&key, bounds);
+}
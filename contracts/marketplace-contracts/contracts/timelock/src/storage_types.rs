@@ -1,5 +1,6 @@
 use soroban_sdk::{contracttype, Address};
 
+pub(crate) const LEDGER_CLOSE_SECONDS: u64 = 5;
 pub(crate) const DAY_IN_LEDGERS: u32 = 17280;
 pub(crate) const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
 pub(crate) const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
@@ -7,10 +8,39 @@ pub(crate) const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_I
 pub(crate) const DEPOSIT_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
 pub(crate) const DEPOSIT_LIFETIME_THRESHOLD: u32 = DEPOSIT_BUMP_AMOUNT - DAY_IN_LEDGERS;
 
+pub(crate) const MAX_PAGE_SIZE: u32 = 100;
+// Keeps a batch deposit within the per-transaction read/write entry limits.
+pub(crate) const MAX_BATCH_SIZE: u32 = 20;
+// Keeps paying out every share of a split deposit within the same limits.
+pub(crate) const MAX_SHARES: u32 = 20;
+
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
+    Admin,
+    Paused,
     LockDuration,
     ClawbackDelay,
+    LockDurationBounds,
+    ClawbackDelayBounds,
+    NextDepositId,
+    // Deposits created before deposit IDs were introduced, keyed by
+    // (depositor, withdrawer, token).
     Deposit(Address, Address, Address),
+    DepositById(u64),
+    DepositorDeposits(Address),
+    WithdrawerDeposits(Address),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[contracttype]
+pub struct DurationBounds {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl DurationBounds {
+    pub fn contains(&self, duration: u64) -> bool {
+        self.min <= duration && duration <= self.max
+    }
 }
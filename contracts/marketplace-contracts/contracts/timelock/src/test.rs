@@ -1,19 +1,31 @@
 #![cfg(test)]
 extern crate std;
 
-use crate::deposit::Deposit;
-use crate::{contract::Timelock, TimelockClient};
+use crate::deposit::{Deposit, LegacyDeposit, Share, TopUpPolicy, UnlockClock};
+use crate::storage_types::{
+    DataKey, DurationBounds, DAY_IN_LEDGERS, INSTANCE_BUMP_AMOUNT, LEDGER_CLOSE_SECONDS,
+};
+use crate::vesting::{VestingSchedule, VestingStep};
+use crate::{contract::Timelock, TimelockClient, TimelockError};
 use soroban_sdk::{
     symbol_short,
-    testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger},
-    token, vec, Address, Env, IntoVal, Symbol,
+    testutils::{
+        storage::{Instance as _, Persistent as _},
+        Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger,
+    },
+    token, vec, Address, Env, IntoVal, Symbol, Vec,
 };
 use token::Client as TokenClient;
 use token::StellarAssetClient as TokenAdminClient;
 
 const LOCK_DURATION: u64 = 24 * 60 * 60;
 const CLAWBACK_DELAY: u64 = LOCK_DURATION * 7;
+const MIN_LOCK_DURATION: u64 = 60 * 60;
+const MAX_LOCK_DURATION: u64 = LOCK_DURATION * 365;
+const MIN_CLAWBACK_DELAY: u64 = LOCK_DURATION;
+const MAX_CLAWBACK_DELAY: u64 = CLAWBACK_DELAY * 4;
 const DEPOSIT_AMOUNT: i128 = 1000;
+const FIRST_DEPOSIT_ID: u64 = 0;
 
 fn create_token_contract<'a>(
     env: &Env,
@@ -26,13 +38,29 @@ fn create_token_contract<'a>(
     )
 }
 
-fn create_timelock_contract<'a>(env: &Env) -> TimelockClient<'a> {
-    let timelock_contract_address = env.register(Timelock, (LOCK_DURATION, CLAWBACK_DELAY));
+fn create_timelock_contract<'a>(env: &Env, admin: &Address) -> TimelockClient<'a> {
+    let timelock_contract_address = env.register(
+        Timelock,
+        (
+            admin.clone(),
+            LOCK_DURATION,
+            CLAWBACK_DELAY,
+            DurationBounds {
+                min: MIN_LOCK_DURATION,
+                max: MAX_LOCK_DURATION,
+            },
+            DurationBounds {
+                min: MIN_CLAWBACK_DELAY,
+                max: MAX_CLAWBACK_DELAY,
+            },
+        ),
+    );
     TimelockClient::new(env, &timelock_contract_address)
 }
 
 struct TimelockTest<'a> {
     env: Env,
+    admin: Address,
     depositor: Address,
     withdrawer: Address,
     token: TokenClient<'a>,
@@ -43,14 +71,16 @@ impl<'a> TimelockTest<'a> {
     fn setup() -> Self {
         let env = Env::default();
         env.mock_all_auths();
+        let admin = Address::generate(&env);
         let depositor = Address::generate(&env);
         let withdrawer = Address::generate(&env);
         let token_admin = Address::generate(&env);
         let (token, token_admin_client) = create_token_contract(&env, &token_admin);
         token_admin_client.mint(&depositor, &DEPOSIT_AMOUNT);
-        let timelock = create_timelock_contract(&env);
+        let timelock = create_timelock_contract(&env, &admin);
         TimelockTest {
             env,
+            admin,
             depositor,
             withdrawer,
             token,
@@ -63,6 +93,8 @@ impl<'a> TimelockTest<'a> {
             &self.withdrawer,
             &self.token.address,
             &DEPOSIT_AMOUNT,
+            &None,
+            &None,
         );
         self
     }
@@ -95,7 +127,14 @@ fn test_deposit_authorization() {
         timelock,
         ..
     } = TimelockTest::setup();
-    timelock.deposit(&depositor, &withdrawer, &token.address, &DEPOSIT_AMOUNT);
+    timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &None,
+        &None,
+    );
     assert_eq!(
         env.auths(),
         [(
@@ -108,7 +147,9 @@ fn test_deposit_authorization() {
                         depositor.clone(),
                         withdrawer,
                         token.address.clone(),
-                        DEPOSIT_AMOUNT
+                        DEPOSIT_AMOUNT,
+                        None::<u64>,
+                        None::<u64>
                     )
                         .into_val(&env)
                 )),
@@ -126,19 +167,44 @@ fn test_deposit_authorization() {
 }
 
 #[test]
-#[should_panic(
-    expected = "TimelockDuplicateDeposit(Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM), Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAFCT4), Contract(CCABDO7UZXYE4W6GVSEGSNNZTKSLFQGKXXQTH6OX7M7GKZ4Z6CUJNGZN))"
-)]
-fn test_deposit_duplicate() {
+fn test_deposit_multiple() {
     let TimelockTest {
+        env,
         depositor,
         withdrawer,
         token,
         timelock,
         ..
     } = TimelockTest::setup();
-    timelock.deposit(&depositor, &withdrawer, &token.address, &DEPOSIT_AMOUNT);
-    timelock.deposit(&depositor, &withdrawer, &token.address, &DEPOSIT_AMOUNT);
+    TokenAdminClient::new(&env, &token.address).mint(&depositor, &DEPOSIT_AMOUNT);
+    let first_id = timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &None,
+        &None,
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION / 2);
+    let second_id = timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &None,
+        &None,
+    );
+    assert_eq!(first_id, FIRST_DEPOSIT_ID);
+    assert_eq!(second_id, FIRST_DEPOSIT_ID + 1);
+    assert_eq!(token.balance(&timelock.address), DEPOSIT_AMOUNT * 2);
+    assert_eq!(
+        timelock.get_deposit(&first_id).unwrap().unlock_timestamp,
+        LOCK_DURATION
+    );
+    assert_eq!(
+        timelock.get_deposit(&second_id).unwrap().unlock_timestamp,
+        LOCK_DURATION / 2 + LOCK_DURATION
+    );
 }
 
 #[test]
@@ -156,6 +222,8 @@ fn test_deposit_insufficient_balance() {
         &withdrawer,
         &token.address,
         &(DEPOSIT_AMOUNT * 2),
+        &None,
+        &None,
     );
 }
 
@@ -171,7 +239,14 @@ fn test_deposit_success() {
     } = TimelockTest::setup();
     let depositor_balance_before = token.balance(&depositor);
     let timelock_balance_before = token.balance(&timelock.address);
-    timelock.deposit(&depositor, &withdrawer, &token.address, &DEPOSIT_AMOUNT);
+    timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &None,
+        &None,
+    );
     assert_eq!(
         token.balance(&depositor),
         depositor_balance_before - DEPOSIT_AMOUNT
@@ -181,13 +256,21 @@ fn test_deposit_success() {
         timelock_balance_before + DEPOSIT_AMOUNT
     );
     assert_eq!(
-        timelock.get_deposit(&depositor, &withdrawer, &token.address),
+        timelock.get_deposit(&FIRST_DEPOSIT_ID),
         Some(Deposit {
+            id: FIRST_DEPOSIT_ID,
             depositor,
             withdrawer,
             token: token.address,
             amount: DEPOSIT_AMOUNT,
-            unlock_timestamp: env.ledger().timestamp() + LOCK_DURATION
+            unlock_timestamp: env.ledger().timestamp() + LOCK_DURATION,
+            schedule: VestingSchedule::Cliff,
+            clock: UnlockClock::Timestamp,
+            withdrawn: 0,
+            lock_duration: LOCK_DURATION,
+            clawback_delay: CLAWBACK_DELAY,
+            claim_needs_approval: false,
+            shares: Vec::new(&env),
         })
     );
 }
@@ -202,7 +285,14 @@ fn test_deposit_events() {
         timelock,
         ..
     } = TimelockTest::setup();
-    timelock.deposit(&depositor, &withdrawer, &token.address, &DEPOSIT_AMOUNT);
+    timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &None,
+        &None,
+    );
     assert_eq!(
         env.events().all(),
         vec![
@@ -227,7 +317,14 @@ fn test_deposit_events() {
                     token.address
                 )
                     .into_val(&env),
-                (DEPOSIT_AMOUNT, env.ledger().timestamp() + LOCK_DURATION).into_val(&env)
+                (
+                    FIRST_DEPOSIT_ID,
+                    DEPOSIT_AMOUNT,
+                    env.ledger().timestamp() + LOCK_DURATION,
+                    LOCK_DURATION,
+                    CLAWBACK_DELAY
+                )
+                    .into_val(&env)
             )
         ]
     );
@@ -237,14 +334,12 @@ fn test_deposit_events() {
 fn test_withdraw_authorization() {
     let TimelockTest {
         env,
-        depositor,
         withdrawer,
-        token,
         timelock,
         ..
     } = TimelockTest::setup_with_deposit();
     env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
-    timelock.withdraw(&depositor, &withdrawer, &token.address);
+    timelock.withdraw(&FIRST_DEPOSIT_ID);
     assert_eq!(
         env.auths(),
         [(
@@ -253,7 +348,7 @@ fn test_withdraw_authorization() {
                 function: AuthorizedFunction::Contract((
                     timelock.address,
                     symbol_short!("withdraw"),
-                    (depositor, withdrawer, token.address).into_val(&env)
+                    (FIRST_DEPOSIT_ID,).into_val(&env)
                 )),
                 sub_invocations: std::vec![]
             }
@@ -262,39 +357,27 @@ fn test_withdraw_authorization() {
 }
 
 #[test]
-#[should_panic(
-    expected = "TimelockNonexistentDeposit(Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM), Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM), Contract(CCABDO7UZXYE4W6GVSEGSNNZTKSLFQGKXXQTH6OX7M7GKZ4Z6CUJNGZN))"
-)]
 fn test_withdraw_non_existent_deposit() {
-    let TimelockTest {
-        depositor,
-        token,
-        timelock,
-        ..
-    } = TimelockTest::setup_with_deposit();
-    timelock.withdraw(&depositor, &depositor, &token.address);
+    let TimelockTest { timelock, .. } = TimelockTest::setup_with_deposit();
+    assert_eq!(
+        timelock.try_withdraw(&(FIRST_DEPOSIT_ID + 1)),
+        Err(Ok(TimelockError::NonexistentDeposit))
+    );
 }
 
 #[test]
-#[should_panic(
-    expected = "TimelockLockedDeposit(Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM), Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAFCT4), Contract(CCABDO7UZXYE4W6GVSEGSNNZTKSLFQGKXXQTH6OX7M7GKZ4Z6CUJNGZN))"
-)]
 fn test_withdraw_locked_deposit() {
-    let TimelockTest {
-        depositor,
-        withdrawer,
-        token,
-        timelock,
-        ..
-    } = TimelockTest::setup_with_deposit();
-    timelock.withdraw(&depositor, &withdrawer, &token.address);
+    let TimelockTest { timelock, .. } = TimelockTest::setup_with_deposit();
+    assert_eq!(
+        timelock.try_withdraw(&FIRST_DEPOSIT_ID),
+        Err(Ok(TimelockError::LockedDeposit))
+    );
 }
 
 #[test]
 fn test_withdraw_success() {
     let TimelockTest {
         env,
-        depositor,
         withdrawer,
         token,
         timelock,
@@ -303,7 +386,7 @@ fn test_withdraw_success() {
     env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
     let timelock_balance_before = token.balance(&timelock.address);
     let withdrawer_balance_before = token.balance(&withdrawer);
-    timelock.withdraw(&depositor, &withdrawer, &token.address);
+    timelock.withdraw(&FIRST_DEPOSIT_ID);
     assert_eq!(
         token.balance(&timelock.address),
         timelock_balance_before - DEPOSIT_AMOUNT
@@ -312,10 +395,7 @@ fn test_withdraw_success() {
         token.balance(&withdrawer),
         withdrawer_balance_before + DEPOSIT_AMOUNT
     );
-    assert_eq!(
-        timelock.get_deposit(&depositor, &withdrawer, &token.address),
-        None
-    );
+    assert_eq!(timelock.get_deposit(&FIRST_DEPOSIT_ID), None);
 }
 
 #[test]
@@ -328,27 +408,14 @@ fn test_withdraw_events() {
         timelock,
         ..
     } = TimelockTest::setup_with_deposit();
-    let _ = timelock.try_withdraw(&depositor, &withdrawer, &token.address);
     assert_eq!(
-        env.events().all(),
-        vec![
-            &env,
-            (
-                timelock.address.clone(),
-                (
-                    Symbol::new(&env, "withdrawal"),
-                    depositor.clone(),
-                    withdrawer.clone(),
-                    token.address.clone(),
-                    false
-                )
-                    .into_val(&env),
-                (DEPOSIT_AMOUNT, LOCK_DURATION).into_val(&env)
-            )
-        ]
+        timelock.try_withdraw(&FIRST_DEPOSIT_ID),
+        Err(Ok(TimelockError::LockedDeposit))
     );
+    // A rejected call is rolled back, so it leaves no events behind.
+    assert_eq!(env.events().all(), vec![&env]);
     env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
-    timelock.withdraw(&depositor, &withdrawer, &token.address);
+    timelock.withdraw(&FIRST_DEPOSIT_ID);
     assert_eq!(
         env.events().all(),
         vec![
@@ -370,11 +437,10 @@ fn test_withdraw_events() {
                     Symbol::new(&env, "withdrawal"),
                     depositor,
                     withdrawer,
-                    token.address,
-                    true
+                    token.address
                 )
                     .into_val(&env),
-                (DEPOSIT_AMOUNT, LOCK_DURATION).into_val(&env)
+                (FIRST_DEPOSIT_ID, DEPOSIT_AMOUNT, LOCK_DURATION).into_val(&env)
             )
         ]
     );
@@ -385,14 +451,12 @@ fn test_clawback_authorization() {
     let TimelockTest {
         env,
         depositor,
-        withdrawer,
-        token,
         timelock,
         ..
     } = TimelockTest::setup_with_deposit();
     env.ledger()
         .with_mut(|li| li.timestamp = LOCK_DURATION + CLAWBACK_DELAY);
-    timelock.clawback(&depositor, &withdrawer, &token.address);
+    timelock.clawback(&FIRST_DEPOSIT_ID);
     assert_eq!(
         env.auths(),
         [(
@@ -401,7 +465,7 @@ fn test_clawback_authorization() {
                 function: AuthorizedFunction::Contract((
                     timelock.address,
                     symbol_short!("clawback"),
-                    (depositor, withdrawer, token.address).into_val(&env)
+                    (FIRST_DEPOSIT_ID,).into_val(&env)
                 )),
                 sub_invocations: std::vec![]
             }
@@ -410,38 +474,25 @@ fn test_clawback_authorization() {
 }
 
 #[test]
-#[should_panic(
-    expected = "TimelockNonexistentDeposit(Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM), Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAFCT4), Contract(CCABDO7UZXYE4W6GVSEGSNNZTKSLFQGKXXQTH6OX7M7GKZ4Z6CUJNGZN))"
-)]
 fn test_clawback_non_existent_deposit() {
-    let TimelockTest {
-        env,
-        depositor,
-        withdrawer,
-        token,
-        timelock,
-        ..
-    } = TimelockTest::setup_with_deposit();
+    let TimelockTest { env, timelock, .. } = TimelockTest::setup_with_deposit();
     env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
-    timelock.withdraw(&depositor, &withdrawer, &token.address);
+    timelock.withdraw(&FIRST_DEPOSIT_ID);
     env.ledger()
         .with_mut(|li| li.timestamp = LOCK_DURATION + CLAWBACK_DELAY);
-    timelock.clawback(&depositor, &withdrawer, &token.address);
+    assert_eq!(
+        timelock.try_clawback(&FIRST_DEPOSIT_ID),
+        Err(Ok(TimelockError::NonexistentDeposit))
+    );
 }
 
 #[test]
-#[should_panic(
-    expected = "TimelockLockedClawback(Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM), Contract(CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAFCT4), Contract(CCABDO7UZXYE4W6GVSEGSNNZTKSLFQGKXXQTH6OX7M7GKZ4Z6CUJNGZN))"
-)]
 fn test_clawback_locked_clawback() {
-    let TimelockTest {
-        depositor,
-        withdrawer,
-        token,
-        timelock,
-        ..
-    } = TimelockTest::setup_with_deposit();
-    timelock.clawback(&depositor, &withdrawer, &token.address);
+    let TimelockTest { timelock, .. } = TimelockTest::setup_with_deposit();
+    assert_eq!(
+        timelock.try_clawback(&FIRST_DEPOSIT_ID),
+        Err(Ok(TimelockError::LockedClawback))
+    );
 }
 
 #[test]
@@ -449,7 +500,6 @@ fn test_clawback_success() {
     let TimelockTest {
         env,
         depositor,
-        withdrawer,
         token,
         timelock,
         ..
@@ -458,7 +508,7 @@ fn test_clawback_success() {
         .with_mut(|li| li.timestamp = LOCK_DURATION + CLAWBACK_DELAY);
     let timelock_balance_before = token.balance(&timelock.address);
     let depositor_balance_before = token.balance(&depositor);
-    timelock.clawback(&depositor, &withdrawer, &token.address);
+    timelock.clawback(&FIRST_DEPOSIT_ID);
     assert_eq!(
         token.balance(&timelock.address),
         timelock_balance_before - DEPOSIT_AMOUNT
@@ -467,10 +517,7 @@ fn test_clawback_success() {
         token.balance(&depositor),
         depositor_balance_before + DEPOSIT_AMOUNT
     );
-    assert_eq!(
-        timelock.get_deposit(&depositor, &withdrawer, &token.address),
-        None
-    );
+    assert_eq!(timelock.get_deposit(&FIRST_DEPOSIT_ID), None);
 }
 
 #[test]
@@ -483,28 +530,15 @@ fn test_clawback_events() {
         timelock,
         ..
     } = TimelockTest::setup_with_deposit();
-    let _ = timelock.try_clawback(&depositor, &withdrawer, &token.address);
     assert_eq!(
-        env.events().all(),
-        vec![
-            &env,
-            (
-                timelock.address.clone(),
-                (
-                    Symbol::new(&env, "clawback"),
-                    depositor.clone(),
-                    withdrawer.clone(),
-                    token.address.clone(),
-                    false
-                )
-                    .into_val(&env),
-                (DEPOSIT_AMOUNT, LOCK_DURATION).into_val(&env)
-            )
-        ]
+        timelock.try_clawback(&FIRST_DEPOSIT_ID),
+        Err(Ok(TimelockError::LockedClawback))
     );
+    // A rejected call is rolled back, so it leaves no events behind.
+    assert_eq!(env.events().all(), vec![&env]);
     env.ledger()
         .with_mut(|li| li.timestamp = LOCK_DURATION + CLAWBACK_DELAY);
-    timelock.clawback(&depositor, &withdrawer, &token.address);
+    timelock.clawback(&FIRST_DEPOSIT_ID);
     assert_eq!(
         env.events().all(),
         vec![
@@ -526,12 +560,1483 @@ fn test_clawback_events() {
                     Symbol::new(&env, "clawback"),
                     depositor,
                     withdrawer,
-                    token.address,
-                    true
+                    token.address
                 )
                     .into_val(&env),
-                (DEPOSIT_AMOUNT, LOCK_DURATION).into_val(&env)
+                (FIRST_DEPOSIT_ID, DEPOSIT_AMOUNT, LOCK_DURATION).into_val(&env)
             )
         ]
     );
 }
+
+#[test]
+fn test_legacy_deposit_migration() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let legacy = LegacyDeposit {
+        depositor: depositor.clone(),
+        withdrawer: withdrawer.clone(),
+        token: token.address.clone(),
+        amount: DEPOSIT_AMOUNT,
+        unlock_timestamp: LOCK_DURATION,
+    };
+    token.transfer(&depositor, &timelock.address, &DEPOSIT_AMOUNT);
+    env.as_contract(&timelock.address, || {
+        env.storage().persistent().set(
+            &DataKey::Deposit(depositor.clone(), withdrawer.clone(), token.address.clone()),
+            &legacy,
+        );
+    });
+    assert_eq!(
+        timelock.get_legacy_deposit(&depositor, &withdrawer, &token.address),
+        Some(legacy.clone())
+    );
+    let deposit_id = timelock.migrate_legacy_deposit(&depositor, &withdrawer, &token.address);
+    assert_eq!(
+        timelock.get_legacy_deposit(&depositor, &withdrawer, &token.address),
+        None
+    );
+    assert_eq!(
+        timelock.get_deposit(&deposit_id),
+        Some(legacy.into_deposit(&env, deposit_id, LOCK_DURATION, CLAWBACK_DELAY))
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw(&deposit_id);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT);
+}
+
+#[test]
+fn test_linear_vesting_withdraw() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let deposit_id = timelock.deposit_with_schedule(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &VestingSchedule::Linear(LOCK_DURATION, LOCK_DURATION * 5),
+        &None,
+    );
+    assert_eq!(
+        timelock.get_deposit(&deposit_id).unwrap().unlock_timestamp,
+        LOCK_DURATION * 5
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION * 2);
+    timelock.withdraw(&deposit_id);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT / 4);
+    assert_eq!(
+        timelock.get_deposit(&deposit_id).unwrap().withdrawn,
+        DEPOSIT_AMOUNT / 4
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION * 3);
+    timelock.withdraw(&deposit_id);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT / 2);
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION * 6);
+    timelock.withdraw(&deposit_id);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT);
+    assert_eq!(timelock.get_deposit(&deposit_id), None);
+}
+
+#[test]
+fn test_step_vesting_withdraw() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let steps = Vec::from_array(
+        &env,
+        [
+            VestingStep {
+                timestamp: LOCK_DURATION,
+                amount: 300,
+            },
+            VestingStep {
+                timestamp: LOCK_DURATION * 2,
+                amount: 700,
+            },
+        ],
+    );
+    let deposit_id = timelock.deposit_with_schedule(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &VestingSchedule::Steps(steps),
+        &None,
+    );
+    assert!(timelock.try_withdraw(&deposit_id).is_err());
+    env.ledger()
+        .with_mut(|li| li.timestamp = LOCK_DURATION * 2 - 1);
+    timelock.withdraw(&deposit_id);
+    assert_eq!(token.balance(&withdrawer), 300);
+    assert!(timelock.try_withdraw(&deposit_id).is_err());
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION * 2);
+    timelock.withdraw(&deposit_id);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT);
+}
+
+#[test]
+fn test_step_vesting_amount_mismatch() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let steps = Vec::from_array(
+        &env,
+        [VestingStep {
+            timestamp: LOCK_DURATION,
+            amount: DEPOSIT_AMOUNT - 1,
+        }],
+    );
+    assert_eq!(
+        timelock.try_deposit_with_schedule(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &DEPOSIT_AMOUNT,
+            &VestingSchedule::Steps(steps),
+            &None,
+        ),
+        Err(Ok(TimelockError::InvalidSchedule))
+    );
+}
+
+#[test]
+fn test_cliff_schedule_rejected() {
+    let TimelockTest {
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    assert_eq!(
+        timelock.try_deposit_with_schedule(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &DEPOSIT_AMOUNT,
+            &VestingSchedule::Cliff,
+            &None,
+        ),
+        Err(Ok(TimelockError::InvalidSchedule))
+    );
+}
+
+#[test]
+fn test_clawback_unvested_remainder() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let vesting_end = CLAWBACK_DELAY * 2;
+    let deposit_id = timelock.deposit_with_schedule(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &VestingSchedule::Linear(0, vesting_end),
+        &None,
+    );
+    env.ledger()
+        .with_mut(|li| li.timestamp = CLAWBACK_DELAY - 1);
+    assert!(timelock.try_clawback(&deposit_id).is_err());
+    env.ledger().with_mut(|li| li.timestamp = CLAWBACK_DELAY);
+    timelock.clawback(&deposit_id);
+    assert_eq!(token.balance(&depositor), DEPOSIT_AMOUNT / 2);
+    let deposit = timelock.get_deposit(&deposit_id).unwrap();
+    assert_eq!(deposit.amount, DEPOSIT_AMOUNT / 2);
+    assert_eq!(deposit.schedule, VestingSchedule::Cliff);
+    // The vested half stays claimable by the withdrawer.
+    timelock.withdraw(&deposit_id);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT / 2);
+    assert_eq!(timelock.get_deposit(&deposit_id), None);
+}
+
+#[test]
+fn test_deposit_custom_durations() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let lock_duration = LOCK_DURATION * 30;
+    let clawback_delay = MIN_CLAWBACK_DELAY;
+    let deposit_id = timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &Some(lock_duration),
+        &Some(clawback_delay),
+    );
+    let deposit = timelock.get_deposit(&deposit_id).unwrap();
+    assert_eq!(deposit.unlock_timestamp, lock_duration);
+    assert_eq!(deposit.lock_duration, lock_duration);
+    assert_eq!(deposit.clawback_delay, clawback_delay);
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    assert!(timelock.try_withdraw(&deposit_id).is_err());
+    env.ledger()
+        .with_mut(|li| li.timestamp = lock_duration + clawback_delay);
+    timelock.clawback(&deposit_id);
+    assert_eq!(token.balance(&depositor), DEPOSIT_AMOUNT);
+}
+
+#[test]
+fn test_deposit_lock_duration_out_of_bounds() {
+    let TimelockTest {
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    assert_eq!(
+        timelock.try_deposit(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &DEPOSIT_AMOUNT,
+            &Some(MAX_LOCK_DURATION + 1),
+            &None,
+        ),
+        Err(Ok(TimelockError::InvalidDuration))
+    );
+}
+
+#[test]
+fn test_deposit_lock_duration_overflow() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    assert_eq!(
+        timelock.try_deposit(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &DEPOSIT_AMOUNT,
+            &Some(u64::MAX),
+            &None,
+        ),
+        Err(Ok(TimelockError::InvalidDuration))
+    );
+    let deposits = Vec::from_array(&env, [(withdrawer.clone(), DEPOSIT_AMOUNT)]);
+    assert_eq!(
+        timelock.try_batch_deposit(
+            &depositor,
+            &token.address,
+            &deposits,
+            &Some(u64::MAX),
+            &None
+        ),
+        Err(Ok(TimelockError::InvalidDuration))
+    );
+    let shares = Vec::from_array(&env, [(withdrawer, 10_000u32)]);
+    assert_eq!(
+        timelock.try_deposit_split(
+            &depositor,
+            &token.address,
+            &DEPOSIT_AMOUNT,
+            &shares,
+            &Some(u64::MAX),
+            &None,
+        ),
+        Err(Ok(TimelockError::InvalidDuration))
+    );
+}
+
+#[test]
+fn test_deposit_zero_amount() {
+    let TimelockTest {
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    assert_eq!(
+        timelock.try_deposit(&depositor, &withdrawer, &token.address, &0, &None, &None),
+        Err(Ok(TimelockError::InvalidAmount))
+    );
+    assert_eq!(
+        timelock.try_deposit_by_ledger(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &0,
+            &DAY_IN_LEDGERS,
+            &None,
+        ),
+        Err(Ok(TimelockError::InvalidAmount))
+    );
+    assert_eq!(
+        timelock.try_deposit_with_schedule(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &0,
+            &VestingSchedule::Linear(0, LOCK_DURATION),
+            &None,
+        ),
+        Err(Ok(TimelockError::InvalidAmount))
+    );
+    assert!(timelock
+        .list_deposits_by_depositor(&depositor, &0, &10)
+        .is_empty());
+}
+
+#[test]
+fn test_deposit_clawback_delay_out_of_bounds() {
+    let TimelockTest {
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    assert_eq!(
+        timelock.try_deposit(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &DEPOSIT_AMOUNT,
+            &None,
+            &Some(MIN_CLAWBACK_DELAY - 1),
+        ),
+        Err(Ok(TimelockError::InvalidDuration))
+    );
+}
+
+#[test]
+fn test_withdraw_partial() {
+    let TimelockTest {
+        env,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw_partial(&FIRST_DEPOSIT_ID, &400);
+    assert_eq!(token.balance(&withdrawer), 400);
+    assert_eq!(
+        timelock.get_deposit(&FIRST_DEPOSIT_ID).unwrap().withdrawn,
+        400
+    );
+    assert!(timelock
+        .try_withdraw_partial(&FIRST_DEPOSIT_ID, &(DEPOSIT_AMOUNT - 399))
+        .is_err());
+    timelock.withdraw_partial(&FIRST_DEPOSIT_ID, &(DEPOSIT_AMOUNT - 400));
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT);
+    assert_eq!(timelock.get_deposit(&FIRST_DEPOSIT_ID), None);
+}
+
+#[test]
+fn test_withdraw_partial_locked_deposit() {
+    let TimelockTest { timelock, .. } = TimelockTest::setup_with_deposit();
+    assert_eq!(
+        timelock.try_withdraw_partial(&FIRST_DEPOSIT_ID, &1),
+        Err(Ok(TimelockError::LockedDeposit))
+    );
+}
+
+#[test]
+fn test_withdraw_partial_events() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw_partial(&FIRST_DEPOSIT_ID, &400);
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                token.address.clone(),
+                (
+                    symbol_short!("transfer"),
+                    timelock.address.clone(),
+                    withdrawer.clone(),
+                    token.name()
+                )
+                    .into_val(&env),
+                (400i128).into_val(&env)
+            ),
+            (
+                timelock.address,
+                (
+                    Symbol::new(&env, "partial_withdrawal"),
+                    depositor,
+                    withdrawer,
+                    token.address
+                )
+                    .into_val(&env),
+                (FIRST_DEPOSIT_ID, 400i128, DEPOSIT_AMOUNT - 400).into_val(&env)
+            )
+        ]
+    );
+}
+
+#[test]
+fn test_top_up_keep_unlock() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    TokenAdminClient::new(&env, &token.address).mint(&depositor, &DEPOSIT_AMOUNT);
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION / 2);
+    timelock.top_up(&FIRST_DEPOSIT_ID, &DEPOSIT_AMOUNT, &TopUpPolicy::KeepUnlock);
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                token.address.clone(),
+                (
+                    symbol_short!("transfer"),
+                    depositor.clone(),
+                    timelock.address.clone(),
+                    token.name()
+                )
+                    .into_val(&env),
+                (DEPOSIT_AMOUNT).into_val(&env)
+            ),
+            (
+                timelock.address.clone(),
+                (
+                    symbol_short!("top_up"),
+                    depositor,
+                    withdrawer.clone(),
+                    token.address.clone()
+                )
+                    .into_val(&env),
+                (
+                    FIRST_DEPOSIT_ID,
+                    DEPOSIT_AMOUNT,
+                    DEPOSIT_AMOUNT * 2,
+                    LOCK_DURATION
+                )
+                    .into_val(&env)
+            )
+        ]
+    );
+    let deposit = timelock.get_deposit(&FIRST_DEPOSIT_ID).unwrap();
+    assert_eq!(deposit.amount, DEPOSIT_AMOUNT * 2);
+    assert_eq!(deposit.unlock_timestamp, LOCK_DURATION);
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw(&FIRST_DEPOSIT_ID);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT * 2);
+}
+
+#[test]
+fn test_top_up_extend_unlock() {
+    let TimelockTest {
+        env,
+        depositor,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    TokenAdminClient::new(&env, &token.address).mint(&depositor, &DEPOSIT_AMOUNT);
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION / 2);
+    timelock.top_up(
+        &FIRST_DEPOSIT_ID,
+        &DEPOSIT_AMOUNT,
+        &TopUpPolicy::ExtendUnlock,
+    );
+    assert_eq!(
+        timelock
+            .get_deposit(&FIRST_DEPOSIT_ID)
+            .unwrap()
+            .unlock_timestamp,
+        LOCK_DURATION / 2 + LOCK_DURATION
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    assert!(timelock.try_withdraw(&FIRST_DEPOSIT_ID).is_err());
+}
+
+#[test]
+fn test_top_up_overflow() {
+    let TimelockTest {
+        env,
+        depositor,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    TokenAdminClient::new(&env, &token.address).mint(&depositor, &i128::MAX);
+    assert_eq!(
+        timelock.try_top_up(&FIRST_DEPOSIT_ID, &i128::MAX, &TopUpPolicy::KeepUnlock),
+        Err(Ok(TimelockError::InvalidAmount))
+    );
+    assert_eq!(token.balance(&depositor), i128::MAX);
+    assert_eq!(
+        timelock.get_deposit(&FIRST_DEPOSIT_ID).unwrap().amount,
+        DEPOSIT_AMOUNT
+    );
+}
+
+#[test]
+fn test_admin_sets_default_durations() {
+    let TimelockTest {
+        env,
+        admin,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    timelock.set_lock_duration(&(LOCK_DURATION * 2));
+    assert_eq!(
+        env.auths(),
+        [(
+            admin,
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    timelock.address.clone(),
+                    Symbol::new(&env, "set_lock_duration"),
+                    (LOCK_DURATION * 2,).into_val(&env)
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    timelock.set_clawback_delay(&MIN_CLAWBACK_DELAY);
+    assert_eq!(timelock.lock_duration(), LOCK_DURATION * 2);
+    assert_eq!(timelock.clawback_delay(), MIN_CLAWBACK_DELAY);
+    // Deposits made before the change keep their original durations.
+    let deposit = timelock.get_deposit(&FIRST_DEPOSIT_ID).unwrap();
+    assert_eq!(deposit.lock_duration, LOCK_DURATION);
+    assert_eq!(deposit.clawback_delay, CLAWBACK_DELAY);
+    assert_eq!(
+        timelock.try_set_lock_duration(&(MAX_LOCK_DURATION + 1)),
+        Err(Ok(TimelockError::InvalidDuration))
+    );
+}
+
+#[test]
+fn test_pause_blocks_deposits_only() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    timelock.pause();
+    assert!(timelock.is_paused());
+    assert_eq!(
+        timelock.try_deposit(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &DEPOSIT_AMOUNT,
+            &None,
+            &None,
+        ),
+        Err(Ok(TimelockError::Paused))
+    );
+    assert_eq!(
+        timelock.try_top_up(&FIRST_DEPOSIT_ID, &1, &TopUpPolicy::KeepUnlock),
+        Err(Ok(TimelockError::Paused))
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw(&FIRST_DEPOSIT_ID);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT);
+    timelock.unpause();
+    assert!(!timelock.is_paused());
+}
+
+#[test]
+fn test_set_admin() {
+    let TimelockTest { env, timelock, .. } = TimelockTest::setup();
+    let new_admin = Address::generate(&env);
+    timelock.set_admin(&new_admin);
+    assert_eq!(timelock.admin(), new_admin);
+    timelock.pause();
+    assert_eq!(
+        env.auths(),
+        [(
+            new_admin,
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    timelock.address.clone(),
+                    symbol_short!("pause"),
+                    ().into_val(&env)
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_list_deposits() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let other_withdrawer = Address::generate(&env);
+    TokenAdminClient::new(&env, &token.address).mint(&depositor, &(DEPOSIT_AMOUNT * 2));
+    for recipient in [&withdrawer, &other_withdrawer, &withdrawer] {
+        timelock.deposit(
+            &depositor,
+            recipient,
+            &token.address,
+            &DEPOSIT_AMOUNT,
+            &None,
+            &None,
+        );
+    }
+    let ids = |deposits: Vec<Deposit>| -> std::vec::Vec<u64> {
+        deposits.iter().map(|deposit| deposit.id).collect()
+    };
+    assert_eq!(
+        ids(timelock.list_deposits_by_depositor(&depositor, &0, &10)),
+        [0, 1, 2]
+    );
+    assert_eq!(
+        ids(timelock.list_deposits_by_depositor(&depositor, &1, &1)),
+        [1]
+    );
+    assert_eq!(
+        ids(timelock.list_deposits_by_depositor(&depositor, &5, &10)),
+        [0u64; 0]
+    );
+    assert_eq!(
+        ids(timelock.list_deposits_by_withdrawer(&withdrawer, &0, &10)),
+        [0, 2]
+    );
+    assert_eq!(
+        ids(timelock.list_deposits_by_withdrawer(&other_withdrawer, &0, &10)),
+        [1]
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw(&0);
+    env.ledger()
+        .with_mut(|li| li.timestamp = LOCK_DURATION + CLAWBACK_DELAY);
+    timelock.clawback(&1);
+    assert_eq!(
+        ids(timelock.list_deposits_by_depositor(&depositor, &0, &10)),
+        [2]
+    );
+    assert_eq!(
+        ids(timelock.list_deposits_by_withdrawer(&withdrawer, &0, &10)),
+        [2]
+    );
+    assert_eq!(
+        ids(timelock.list_deposits_by_withdrawer(&other_withdrawer, &0, &10)),
+        [0u64; 0]
+    );
+}
+
+#[test]
+fn test_batch_deposit() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let other_withdrawer = Address::generate(&env);
+    let batch = vec![
+        &env,
+        (withdrawer.clone(), 600i128),
+        (other_withdrawer.clone(), 400i128),
+    ];
+    let ids = timelock.batch_deposit(&depositor, &token.address, &batch, &None, &None);
+    assert_eq!(ids, vec![&env, 0u64, 1u64]);
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                token.address.clone(),
+                (
+                    symbol_short!("transfer"),
+                    depositor.clone(),
+                    timelock.address.clone(),
+                    token.name()
+                )
+                    .into_val(&env),
+                (DEPOSIT_AMOUNT).into_val(&env)
+            ),
+            (
+                timelock.address.clone(),
+                (
+                    symbol_short!("deposit"),
+                    depositor.clone(),
+                    withdrawer.clone(),
+                    token.address.clone()
+                )
+                    .into_val(&env),
+                (0u64, 600i128, LOCK_DURATION, LOCK_DURATION, CLAWBACK_DELAY).into_val(&env)
+            ),
+            (
+                timelock.address.clone(),
+                (
+                    symbol_short!("deposit"),
+                    depositor.clone(),
+                    other_withdrawer.clone(),
+                    token.address.clone()
+                )
+                    .into_val(&env),
+                (1u64, 400i128, LOCK_DURATION, LOCK_DURATION, CLAWBACK_DELAY).into_val(&env)
+            ),
+            (
+                timelock.address.clone(),
+                (
+                    Symbol::new(&env, "batch_deposit"),
+                    depositor,
+                    token.address.clone()
+                )
+                    .into_val(&env),
+                (2u32, DEPOSIT_AMOUNT).into_val(&env)
+            )
+        ]
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw(&1);
+    assert_eq!(token.balance(&other_withdrawer), 400);
+}
+
+#[test]
+fn test_batch_deposit_invalid() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let negative_entry = vec![
+        &env,
+        (withdrawer.clone(), DEPOSIT_AMOUNT * 2),
+        (depositor.clone(), -DEPOSIT_AMOUNT),
+    ];
+    assert_eq!(
+        timelock.try_batch_deposit(&depositor, &token.address, &negative_entry, &None, &None),
+        Err(Ok(TimelockError::InvalidAmount))
+    );
+    let overflowing = vec![
+        &env,
+        (withdrawer.clone(), i128::MAX),
+        (withdrawer.clone(), 1),
+    ];
+    assert_eq!(
+        timelock.try_batch_deposit(&depositor, &token.address, &overflowing, &None, &None),
+        Err(Ok(TimelockError::InvalidAmount))
+    );
+    let mut too_large = Vec::new(&env);
+    for _ in 0..21 {
+        too_large.push_back((withdrawer.clone(), 1i128));
+    }
+    assert_eq!(
+        timelock.try_batch_deposit(&depositor, &token.address, &too_large, &None, &None),
+        Err(Ok(TimelockError::BatchTooLarge))
+    );
+    assert_eq!(token.balance(&depositor), DEPOSIT_AMOUNT);
+}
+
+#[test]
+fn test_release_early() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    timelock.release_early(&FIRST_DEPOSIT_ID);
+    assert_eq!(
+        env.auths(),
+        [(
+            depositor.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    timelock.address.clone(),
+                    Symbol::new(&env, "release_early"),
+                    (FIRST_DEPOSIT_ID,).into_val(&env)
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT);
+    assert_eq!(timelock.get_deposit(&FIRST_DEPOSIT_ID), None);
+    assert_eq!(
+        timelock.list_deposits_by_withdrawer(&withdrawer, &0, &10),
+        Vec::new(&env)
+    );
+    assert_eq!(
+        timelock.try_release_early(&FIRST_DEPOSIT_ID),
+        Err(Ok(TimelockError::NonexistentDeposit))
+    );
+}
+
+#[test]
+fn test_return_to_depositor() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    timelock.return_to_depositor(&FIRST_DEPOSIT_ID);
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                token.address.clone(),
+                (
+                    symbol_short!("transfer"),
+                    timelock.address.clone(),
+                    depositor.clone(),
+                    token.name()
+                )
+                    .into_val(&env),
+                (DEPOSIT_AMOUNT).into_val(&env)
+            ),
+            (
+                timelock.address.clone(),
+                (
+                    Symbol::new(&env, "return_to_depositor"),
+                    depositor.clone(),
+                    withdrawer.clone(),
+                    token.address.clone()
+                )
+                    .into_val(&env),
+                (FIRST_DEPOSIT_ID, DEPOSIT_AMOUNT).into_val(&env)
+            )
+        ]
+    );
+    assert_eq!(token.balance(&depositor), DEPOSIT_AMOUNT);
+    assert_eq!(timelock.get_deposit(&FIRST_DEPOSIT_ID), None);
+}
+
+#[test]
+fn test_transfer_claim() {
+    let TimelockTest {
+        env,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    let financier = Address::generate(&env);
+    timelock.transfer_claim(&FIRST_DEPOSIT_ID, &financier);
+    assert_eq!(
+        env.auths(),
+        [(
+            withdrawer.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    timelock.address.clone(),
+                    Symbol::new(&env, "transfer_claim"),
+                    (FIRST_DEPOSIT_ID, financier.clone()).into_val(&env)
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    let deposit = timelock.get_deposit(&FIRST_DEPOSIT_ID).unwrap();
+    assert_eq!(deposit.withdrawer, financier);
+    assert_eq!(deposit.unlock_timestamp, LOCK_DURATION);
+    assert_eq!(
+        timelock.list_deposits_by_withdrawer(&withdrawer, &0, &10),
+        Vec::new(&env)
+    );
+    assert_eq!(
+        timelock.list_deposits_by_withdrawer(&financier, &0, &10),
+        vec![&env, deposit]
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw(&FIRST_DEPOSIT_ID);
+    assert_eq!(token.balance(&financier), DEPOSIT_AMOUNT);
+    assert_eq!(token.balance(&withdrawer), 0);
+}
+
+#[test]
+fn test_transfer_claim_requires_depositor_approval() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    let financier = Address::generate(&env);
+    timelock.set_claim_transfer_approval(&FIRST_DEPOSIT_ID, &true);
+    timelock.transfer_claim(&FIRST_DEPOSIT_ID, &financier);
+    let invocation = AuthorizedInvocation {
+        function: AuthorizedFunction::Contract((
+            timelock.address.clone(),
+            Symbol::new(&env, "transfer_claim"),
+            (FIRST_DEPOSIT_ID, financier).into_val(&env),
+        )),
+        sub_invocations: std::vec![],
+    };
+    assert_eq!(
+        env.auths(),
+        [(withdrawer, invocation.clone()), (depositor, invocation)]
+    );
+}
+
+#[test]
+fn test_claim_transfer_approval_needs_withdrawer() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup_with_deposit();
+    let invocation = |required: bool| AuthorizedInvocation {
+        function: AuthorizedFunction::Contract((
+            timelock.address.clone(),
+            Symbol::new(&env, "set_claim_transfer_approval"),
+            (FIRST_DEPOSIT_ID, required).into_val(&env),
+        )),
+        sub_invocations: std::vec![],
+    };
+
+    // Requiring approval takes a right away from the withdrawer, who has to agree.
+    timelock.set_claim_transfer_approval(&FIRST_DEPOSIT_ID, &true);
+    assert_eq!(
+        env.auths(),
+        [
+            (depositor.clone(), invocation(true)),
+            (withdrawer.clone(), invocation(true))
+        ]
+    );
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                timelock.address.clone(),
+                (
+                    Symbol::new(&env, "claim_transfer_approval"),
+                    depositor.clone(),
+                    withdrawer.clone(),
+                    token.address.clone()
+                )
+                    .into_val(&env),
+                (FIRST_DEPOSIT_ID, true).into_val(&env)
+            )
+        ]
+    );
+    assert!(
+        timelock
+            .get_deposit(&FIRST_DEPOSIT_ID)
+            .unwrap()
+            .claim_needs_approval
+    );
+
+    // Dropping the requirement only gives the withdrawer more freedom.
+    timelock.set_claim_transfer_approval(&FIRST_DEPOSIT_ID, &false);
+    assert_eq!(env.auths(), [(depositor, invocation(false))]);
+    assert!(
+        !timelock
+            .get_deposit(&FIRST_DEPOSIT_ID)
+            .unwrap()
+            .claim_needs_approval
+    );
+}
+
+#[test]
+fn test_deposit_ttl_covers_clawback_horizon() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let lock_duration = LOCK_DURATION * 100;
+    let id = timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &(DEPOSIT_AMOUNT / 2),
+        &Some(lock_duration),
+        &None,
+    );
+    let horizon_in_ledgers = ((lock_duration + CLAWBACK_DELAY) / LEDGER_CLOSE_SECONDS) as u32;
+    env.as_contract(&timelock.address, || {
+        let storage = env.storage().persistent();
+        assert!(storage.get_ttl(&DataKey::DepositById(id)) >= horizon_in_ledgers);
+        assert!(
+            storage.get_ttl(&DataKey::DepositorDeposits(depositor.clone())) >= horizon_in_ledgers
+        );
+        assert!(
+            storage.get_ttl(&DataKey::WithdrawerDeposits(withdrawer.clone())) >= horizon_in_ledgers
+        );
+    });
+
+    // Locks longer than the network allows are kept for the maximum entry TTL.
+    let id = timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &(DEPOSIT_AMOUNT / 2),
+        &Some(MAX_LOCK_DURATION),
+        &Some(MAX_CLAWBACK_DELAY),
+    );
+    env.as_contract(&timelock.address, || {
+        assert_eq!(
+            env.storage()
+                .persistent()
+                .get_ttl(&DataKey::DepositById(id)),
+            env.storage().max_ttl()
+        );
+    });
+}
+
+#[test]
+fn test_extend_deposit_ttl() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    // The horizon of this deposit is past the maximum entry TTL, so it needs extending.
+    let id = timelock.deposit(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &Some(MAX_LOCK_DURATION),
+        &Some(MAX_CLAWBACK_DELAY),
+    );
+    let max_ttl = env.storage().max_ttl();
+    // Stay within the instance TTL so that the contract instance itself is still live.
+    let elapsed_ledgers = INSTANCE_BUMP_AMOUNT - 2 * DAY_IN_LEDGERS;
+    env.ledger().with_mut(|li| {
+        li.sequence_number += elapsed_ledgers;
+        li.timestamp += elapsed_ledgers as u64 * LEDGER_CLOSE_SECONDS;
+    });
+    let ttls = || {
+        env.as_contract(&timelock.address, || {
+            let storage = env.storage().persistent();
+            (
+                storage.get_ttl(&DataKey::DepositById(id)),
+                storage.get_ttl(&DataKey::DepositorDeposits(depositor.clone())),
+                storage.get_ttl(&DataKey::WithdrawerDeposits(withdrawer.clone())),
+            )
+        })
+    };
+    let remaining = max_ttl - elapsed_ledgers;
+    assert_eq!(ttls(), (remaining, remaining, remaining));
+
+    // No authorization is needed to keep a deposit alive.
+    env.set_auths(&[]);
+    timelock.extend_deposit_ttl(&id);
+    assert_eq!(ttls(), (max_ttl, max_ttl, max_ttl));
+    env.as_contract(&timelock.address, || {
+        assert_eq!(env.storage().instance().get_ttl(), INSTANCE_BUMP_AMOUNT);
+    });
+    assert_eq!(
+        timelock.try_extend_deposit_ttl(&(id + 1)),
+        Err(Ok(TimelockError::NonexistentDeposit))
+    );
+}
+
+#[test]
+fn test_split_deposit() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let second = Address::generate(&env);
+    let third = Address::generate(&env);
+    let shares = vec![
+        &env,
+        (withdrawer.clone(), 3333u32),
+        (second.clone(), 3333u32),
+        (third.clone(), 3334u32),
+    ];
+    let id = timelock.deposit_split(
+        &depositor,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &shares,
+        &None,
+        &None,
+    );
+    let expected_shares = vec![
+        &env,
+        Share {
+            beneficiary: withdrawer.clone(),
+            bps: 3333,
+            claimed: false,
+        },
+        Share {
+            beneficiary: second.clone(),
+            bps: 3333,
+            claimed: false,
+        },
+        Share {
+            beneficiary: third.clone(),
+            bps: 3334,
+            claimed: false,
+        },
+    ];
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                token.address.clone(),
+                (
+                    symbol_short!("transfer"),
+                    depositor.clone(),
+                    timelock.address.clone(),
+                    token.name()
+                )
+                    .into_val(&env),
+                (DEPOSIT_AMOUNT).into_val(&env)
+            ),
+            (
+                timelock.address.clone(),
+                (
+                    Symbol::new(&env, "split_deposit"),
+                    depositor.clone(),
+                    token.address.clone()
+                )
+                    .into_val(&env),
+                (
+                    id,
+                    DEPOSIT_AMOUNT,
+                    LOCK_DURATION,
+                    CLAWBACK_DELAY,
+                    expected_shares.clone()
+                )
+                    .into_val(&env)
+            )
+        ]
+    );
+    assert_eq!(timelock.get_deposit(&id).unwrap().shares, expected_shares);
+    assert_eq!(
+        timelock.list_deposits_by_withdrawer(&third, &0, &10).len(),
+        1
+    );
+    assert_eq!(
+        timelock.try_withdraw_share(&id, &second),
+        Err(Ok(TimelockError::LockedDeposit))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    timelock.withdraw_share(&id, &second);
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                token.address.clone(),
+                (
+                    symbol_short!("transfer"),
+                    timelock.address.clone(),
+                    second.clone(),
+                    token.name()
+                )
+                    .into_val(&env),
+                (333i128).into_val(&env)
+            ),
+            (
+                timelock.address.clone(),
+                (
+                    Symbol::new(&env, "share_withdrawal"),
+                    depositor.clone(),
+                    second.clone(),
+                    token.address.clone()
+                )
+                    .into_val(&env),
+                (id, 333i128).into_val(&env)
+            )
+        ]
+    );
+    assert_eq!(token.balance(&second), 333);
+    assert_eq!(
+        timelock.try_withdraw_share(&id, &second),
+        Err(Ok(TimelockError::NoShare))
+    );
+    assert_eq!(
+        timelock.try_withdraw_share(&id, &depositor),
+        Err(Ok(TimelockError::NoShare))
+    );
+    assert_eq!(
+        timelock.list_deposits_by_withdrawer(&second, &0, &10),
+        Vec::new(&env)
+    );
+
+    // The last share receives what rounding the other shares down left over.
+    timelock.withdraw_share(&id, &third);
+    timelock.withdraw_share(&id, &withdrawer);
+    assert_eq!(token.balance(&third), 334);
+    assert_eq!(token.balance(&withdrawer), 333);
+    assert_eq!(timelock.get_deposit(&id), None);
+    assert_eq!(
+        timelock.list_deposits_by_withdrawer(&withdrawer, &0, &10),
+        Vec::new(&env)
+    );
+}
+
+#[test]
+fn test_split_deposit_clawback() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let other_withdrawer = Address::generate(&env);
+    let shares = vec![
+        &env,
+        (withdrawer.clone(), 7500u32),
+        (other_withdrawer.clone(), 2500u32),
+    ];
+    let id = timelock.deposit_split(
+        &depositor,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &shares,
+        &None,
+        &None,
+    );
+    env.ledger()
+        .with_mut(|li| li.timestamp = LOCK_DURATION + CLAWBACK_DELAY);
+    timelock.withdraw_share(&id, &other_withdrawer);
+    timelock.clawback(&id);
+    assert_eq!(token.balance(&other_withdrawer), 250);
+    assert_eq!(token.balance(&depositor), 750);
+    assert_eq!(timelock.get_deposit(&id), None);
+    assert_eq!(
+        timelock.list_deposits_by_withdrawer(&withdrawer, &0, &10),
+        Vec::new(&env)
+    );
+}
+
+#[test]
+fn test_split_deposit_invalid() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let other_withdrawer = Address::generate(&env);
+    for shares in [
+        Vec::new(&env),
+        vec![&env, (withdrawer.clone(), 9999u32)],
+        vec![
+            &env,
+            (withdrawer.clone(), 10_000u32),
+            (other_withdrawer.clone(), 0u32),
+        ],
+        vec![
+            &env,
+            (withdrawer.clone(), 5000u32),
+            (withdrawer.clone(), 5000u32),
+        ],
+    ] {
+        assert_eq!(
+            timelock.try_deposit_split(
+                &depositor,
+                &token.address,
+                &DEPOSIT_AMOUNT,
+                &shares,
+                &None,
+                &None
+            ),
+            Err(Ok(TimelockError::InvalidShares))
+        );
+    }
+
+    // Single-withdrawer operations are not available on split deposits.
+    let shares = vec![
+        &env,
+        (withdrawer.clone(), 5000u32),
+        (other_withdrawer.clone(), 5000u32),
+    ];
+    let id = timelock.deposit_split(
+        &depositor,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &shares,
+        &None,
+        &None,
+    );
+    env.ledger().with_mut(|li| li.timestamp = LOCK_DURATION);
+    assert_eq!(
+        timelock.try_withdraw(&id),
+        Err(Ok(TimelockError::SplitDeposit))
+    );
+    assert_eq!(
+        timelock.try_transfer_claim(&id, &depositor),
+        Err(Ok(TimelockError::SplitDeposit))
+    );
+    assert_eq!(
+        timelock.try_return_to_depositor(&id),
+        Err(Ok(TimelockError::SplitDeposit))
+    );
+
+    // Releasing early pays every beneficiary their share.
+    timelock.release_early(&id);
+    assert_eq!(token.balance(&withdrawer), 500);
+    assert_eq!(token.balance(&other_withdrawer), 500);
+    assert_eq!(timelock.get_deposit(&id), None);
+}
+
+#[test]
+fn test_deposit_by_ledger() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let lock_ledgers = (LOCK_DURATION / LEDGER_CLOSE_SECONDS) as u32;
+    let clawback_delay_ledgers = CLAWBACK_DELAY / LEDGER_CLOSE_SECONDS;
+    let start_sequence = env.ledger().sequence() as u64;
+    let id = timelock.deposit_by_ledger(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &(DEPOSIT_AMOUNT / 2),
+        &lock_ledgers,
+        &None,
+    );
+    let deposit = timelock.get_deposit(&id).unwrap();
+    assert_eq!(deposit.clock, UnlockClock::LedgerSequence);
+    assert_eq!(
+        deposit.unlock_timestamp,
+        start_sequence + lock_ledgers as u64
+    );
+    assert_eq!(deposit.lock_duration, lock_ledgers as u64);
+    assert_eq!(deposit.clawback_delay, clawback_delay_ledgers);
+
+    // Only the ledger sequence counts towards the unlock.
+    env.ledger()
+        .with_mut(|li| li.timestamp = LOCK_DURATION + CLAWBACK_DELAY);
+    assert_eq!(
+        timelock.try_withdraw(&id),
+        Err(Ok(TimelockError::LockedDeposit))
+    );
+    env.ledger()
+        .with_mut(|li| li.sequence_number += lock_ledgers);
+    timelock.withdraw(&id);
+    assert_eq!(token.balance(&withdrawer), DEPOSIT_AMOUNT / 2);
+
+    assert_eq!(
+        timelock.try_deposit_by_ledger(
+            &depositor,
+            &withdrawer,
+            &token.address,
+            &(DEPOSIT_AMOUNT / 2),
+            &1,
+            &None
+        ),
+        Err(Ok(TimelockError::InvalidDuration))
+    );
+}
+
+#[test]
+fn test_clawback_by_ledger() {
+    let TimelockTest {
+        env,
+        depositor,
+        withdrawer,
+        token,
+        timelock,
+        ..
+    } = TimelockTest::setup();
+    let lock_ledgers = (LOCK_DURATION / LEDGER_CLOSE_SECONDS) as u32;
+    let clawback_delay_ledgers = (MIN_CLAWBACK_DELAY / LEDGER_CLOSE_SECONDS) as u32;
+    let id = timelock.deposit_by_ledger(
+        &depositor,
+        &withdrawer,
+        &token.address,
+        &DEPOSIT_AMOUNT,
+        &lock_ledgers,
+        &Some(clawback_delay_ledgers),
+    );
+    env.ledger()
+        .with_mut(|li| li.timestamp = LOCK_DURATION + MAX_CLAWBACK_DELAY);
+    assert_eq!(
+        timelock.try_clawback(&id),
+        Err(Ok(TimelockError::LockedClawback))
+    );
+    env.ledger()
+        .with_mut(|li| li.sequence_number += lock_ledgers + clawback_delay_ledgers);
+    timelock.clawback(&id);
+    assert_eq!(token.balance(&depositor), DEPOSIT_AMOUNT);
+    assert_eq!(timelock.get_deposit(&id), None);
+}
@@ -0,0 +1,90 @@
+use soroban_sdk::{contracttype, Vec};
+
+#[derive(Clone, PartialEq, Debug)]
+#[contracttype]
+pub struct VestingStep {
+    pub timestamp: u64,
+    pub amount: i128,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[contracttype]
+pub enum VestingSchedule {
+    /// The whole amount unlocks at once at the deposit's `unlock_timestamp`.
+    Cliff,
+    /// The amount vests linearly from the start timestamp to the end timestamp.
+    Linear(u64, u64),
+    /// Each step unlocks its amount at its timestamp. Steps are strictly ordered by
+    /// timestamp and their amounts add up to the deposit amount.
+    Steps(Vec<VestingStep>),
+}
+
+impl VestingSchedule {
+    pub fn is_valid(&self, amount: i128) -> bool {
+        match self {
+            VestingSchedule::Cliff => true,
+            VestingSchedule::Linear(start, end) => start < end,
+            VestingSchedule::Steps(steps) => {
+                if steps.is_empty() {
+                    return false;
+                }
+                let mut total: i128 = 0;
+                let mut previous: Option<u64> = None;
+                for step in steps.iter() {
+                    if step.amount <= 0 || previous.is_some_and(|ts| ts >= step.timestamp) {
+                        return false;
+                    }
+                    previous = Some(step.timestamp);
+                    total += step.amount;
+                }
+                total == amount
+            }
+        }
+    }
+
+    /// Timestamp at which the first part of the deposit starts vesting, if the
+    /// schedule defines one. A cliff starts and ends at the deposit's `unlock_timestamp`.
+    pub fn start_timestamp(&self) -> Option<u64> {
+        match self {
+            VestingSchedule::Cliff => None,
+            VestingSchedule::Linear(start, _) => Some(*start),
+            VestingSchedule::Steps(steps) => steps.first().map(|step| step.timestamp),
+        }
+    }
+
+    /// Timestamp at which the whole deposit has vested, if the schedule defines one.
+    pub fn end_timestamp(&self) -> Option<u64> {
+        match self {
+            VestingSchedule::Cliff => None,
+            VestingSchedule::Linear(_, end) => Some(*end),
+            VestingSchedule::Steps(steps) => steps.last().map(|step| step.timestamp),
+        }
+    }
+
+    /// Amount out of `amount` that has vested at `now`, including anything already withdrawn.
+    pub fn vested_amount(&self, amount: i128, unlock_timestamp: u64, now: u64) -> i128 {
+        match self {
+            VestingSchedule::Cliff => {
+                if now < unlock_timestamp {
+                    0
+                } else {
+                    amount
+                }
+            }
+            VestingSchedule::Linear(start, end) => {
+                if now <= *start {
+                    0
+                } else if now >= *end {
+                    amount
+                } else {
+                    amount * (now - start) as i128 / (end - start) as i128
+                }
+            }
+            VestingSchedule::Steps(steps) => steps
+                .iter()
+                .take_while(|step| step.timestamp <= now)
+                .map(|step| step.amount)
+                .sum(),
+        }
+    }
+}